@@ -0,0 +1,327 @@
+/// Extraction of archived tool releases (.tar.gz, .tar.xz, .tar, .zip) and of
+/// single gzip'd executables.
+// Extern imports
+use flate2::read::GzDecoder;
+
+// std imports
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufReader, Cursor, Read},
+    path::{Component, Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveKind {
+    TarGz,
+    TarXz,
+    Tar,
+    Zip,
+    /// A single gzip'd file rather than a tarball
+    Gz,
+}
+
+fn is_tar(head: &[u8]) -> bool {
+    head.len() >= 262 && &head[257..262] == b"ustar"
+}
+
+/// Sniffs the magic bytes of `path`. Returns None for anything that isn't a
+/// known archive, which is then installed as-is.
+pub fn detect(path: &Path) -> io::Result<Option<ArchiveKind>> {
+    let mut head = Vec::with_capacity(512);
+    File::open(path)?.take(512).read_to_end(&mut head)?;
+    let kind = if head.starts_with(&[0x1f, 0x8b]) {
+        // Only the start of the tarball tells it from a gzip'd executable
+        let mut inner = Vec::with_capacity(512);
+        GzDecoder::new(File::open(path)?)
+            .take(512)
+            .read_to_end(&mut inner)?;
+        match is_tar(&inner) {
+            true => Some(ArchiveKind::TarGz),
+            false => Some(ArchiveKind::Gz),
+        }
+    } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Some(ArchiveKind::TarXz)
+    } else if head.starts_with(b"PK\x03\x04") {
+        Some(ArchiveKind::Zip)
+    } else if is_tar(&head) {
+        Some(ArchiveKind::Tar)
+    } else {
+        None
+    };
+    Ok(kind)
+}
+
+/// Drops the first `n` normal components of `path`, like tar's --strip-components.
+fn strip(path: &Path, n: usize) -> Option<PathBuf> {
+    let rest: PathBuf = path
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .skip(n)
+        .collect();
+    if rest.as_os_str().is_empty() {
+        None
+    } else {
+        Some(rest)
+    }
+}
+
+/// A spec containing a '/' must match the whole (stripped) entry path,
+/// otherwise it only has to match the file name, with or without '.exe'.
+fn spec_matches(spec: &str, entry: &Path) -> bool {
+    if spec.contains('/') {
+        return entry == Path::new(spec);
+    }
+    match entry.file_name().and_then(|f| f.to_str()) {
+        Some(file) => file == spec || file.strip_suffix(".exe") == Some(spec),
+        None => false,
+    }
+}
+
+struct Extractor<'a> {
+    specs: &'a [String],
+    strip_components: usize,
+    dest_dir: &'a Path,
+    found: Vec<bool>,
+    written: Vec<PathBuf>,
+}
+
+impl Extractor<'_> {
+    fn entry<R: Read>(&mut self, path: &Path, reader: &mut R) -> io::Result<()> {
+        let Some(stripped) = strip(path, self.strip_components) else {
+            return Ok(());
+        };
+        let Some(index) = self.specs.iter().position(|s| spec_matches(s, &stripped)) else {
+            return Ok(());
+        };
+        // Only the file name is kept, so entries can never escape dest_dir
+        let dest = self.dest_dir.join(stripped.file_name().unwrap());
+        io::copy(reader, &mut File::create(&dest)?)?;
+        self.found[index] = true;
+        self.written.push(dest);
+        Ok(())
+    }
+
+    fn tar<R: Read>(&mut self, reader: R) -> io::Result<()> {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry.path()?.into_owned();
+            self.entry(&path, &mut entry)?;
+        }
+        Ok(())
+    }
+
+    fn zip(&mut self, file: File) -> Result<(), Box<dyn Error>> {
+        let mut archive = zip::ZipArchive::new(file)?;
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            if !entry.is_file() {
+                continue;
+            }
+            if let Some(path) = entry.enclosed_name() {
+                self.entry(&path, &mut entry)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Extracts the entries of `archive` selected by `specs` into `dest_dir`,
+/// returning the paths that were written.
+pub fn extract_bins(
+    archive: &Path,
+    kind: ArchiveKind,
+    specs: &[String],
+    strip_components: usize,
+    dest_dir: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut ex = Extractor {
+        specs,
+        strip_components,
+        dest_dir,
+        found: vec![false; specs.len()],
+        written: vec![],
+    };
+    let file = File::open(archive)?;
+    match kind {
+        ArchiveKind::TarGz => ex.tar(GzDecoder::new(BufReader::new(file)))?,
+        ArchiveKind::TarXz => {
+            let mut tarball = Vec::new();
+            lzma_rs::xz_decompress(&mut BufReader::new(file), &mut tarball)
+                .map_err(|e| format!("Bad xz archive: {:?}", e))?;
+            ex.tar(Cursor::new(tarball))?
+        }
+        ArchiveKind::Tar => ex.tar(BufReader::new(file))?,
+        ArchiveKind::Zip => ex.zip(file)?,
+        ArchiveKind::Gz => {
+            let [spec] = specs else {
+                return Err("A gzip'd file holds a single executable, list at most one 'bin'".into());
+            };
+            let name = Path::new(spec).file_name().ok_or("Invalid bin name")?;
+            let dest = dest_dir.join(name);
+            io::copy(&mut GzDecoder::new(BufReader::new(file)), &mut File::create(&dest)?)?;
+            ex.found[0] = true;
+            ex.written.push(dest);
+        }
+    }
+    let missing: Vec<&str> = specs
+        .iter()
+        .zip(&ex.found)
+        .filter(|(_, found)| !**found)
+        .map(|(spec, _)| spec.as_str())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("Not found in archive: {}", missing.join(", ")).into());
+    }
+    Ok(ex.written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::{env, fs, io::Write, process};
+
+    /// An empty directory of its own for a test, removed with it.
+    struct Scratch(PathBuf);
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn scratch(name: &str) -> Scratch {
+        let dir = env::temp_dir().join(format!("zzz-archive-{0}-{1}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Scratch(dir)
+    }
+
+    fn tarball(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder.append_data(&mut header, path, data.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn zipfile(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        for (path, data) in entries {
+            writer.start_file(*path, options).unwrap();
+            writer.write_all(data.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// Writes `archive` to `dir` and extracts `specs` from it into `dir/out`,
+    /// returning the extracted file names.
+    fn extract(
+        dir: &Path,
+        archive: &[u8],
+        specs: &[&str],
+        strip_components: usize,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let path = dir.join("download");
+        fs::write(&path, archive).unwrap();
+        let out = dir.join("out");
+        fs::create_dir_all(&out).unwrap();
+        let kind = detect(&path)?.ok_or("Not an archive")?;
+        let specs: Vec<String> = specs.iter().map(|s| s.to_string()).collect();
+        let written = extract_bins(&path, kind, &specs, strip_components, &out)?;
+        Ok(written
+            .iter()
+            .map(|p| p.strip_prefix(&out).unwrap().to_string_lossy().to_string())
+            .collect())
+    }
+
+    #[test]
+    fn strips_leading_components() {
+        assert_eq!(strip(Path::new("a/b/c"), 0), Some(PathBuf::from("a/b/c")));
+        assert_eq!(strip(Path::new("a/b/c"), 1), Some(PathBuf::from("b/c")));
+        assert_eq!(strip(Path::new("./a/b"), 1), Some(PathBuf::from("b")));
+        assert_eq!(strip(Path::new("a/b"), 2), None);
+    }
+
+    #[test]
+    fn specs_match_names_or_paths() {
+        assert!(spec_matches("tool", Path::new("bin/tool")));
+        assert!(spec_matches("tool", Path::new("bin/tool.exe")));
+        assert!(!spec_matches("tool", Path::new("bin/tool.sh")));
+        assert!(!spec_matches("tool", Path::new("tool/README")));
+        assert!(spec_matches("bin/tool", Path::new("bin/tool")));
+        assert!(!spec_matches("bin/tool", Path::new("other/bin/tool")));
+    }
+
+    #[test]
+    fn detects_kinds() {
+        let Scratch(ref dir) = scratch("detect");
+        let tar = tarball(&[("tool", "#!/bin/sh\n")]);
+        for (data, kind) in [
+            (gzip(&tar), Some(ArchiveKind::TarGz)),
+            (gzip(b"\x7fELF"), Some(ArchiveKind::Gz)),
+            (tar.clone(), Some(ArchiveKind::Tar)),
+            (zipfile(&[("tool", "x")]), Some(ArchiveKind::Zip)),
+            (b"\x7fELF".to_vec(), None),
+        ] {
+            let path = dir.join("download");
+            fs::write(&path, data).unwrap();
+            assert_eq!(detect(&path).unwrap(), kind);
+        }
+    }
+
+    #[test]
+    fn extracts_from_tarballs() {
+        let Scratch(ref dir) = scratch("tar");
+        let tar = tarball(&[
+            ("tool-1.0/README", "read me"),
+            ("tool-1.0/bin/tool", "#!/bin/sh\n"),
+            ("tool-1.0/bin/helper", "#!/bin/sh\n"),
+        ]);
+        assert_eq!(extract(dir, &tar, &["tool"], 0).unwrap(), ["tool"]);
+        assert_eq!(extract(dir, &gzip(&tar), &["bin/helper"], 1).unwrap(), ["helper"]);
+        assert_eq!(fs::read_to_string(dir.join("out/helper")).unwrap(), "#!/bin/sh\n");
+        // Without stripping the path spec has to include the top directory
+        assert!(extract(dir, &tar, &["bin/helper"], 0).is_err());
+    }
+
+    #[test]
+    fn extracts_exe_from_zips() {
+        let Scratch(ref dir) = scratch("zip");
+        let zip = zipfile(&[("tool/tool.exe", "MZ"), ("tool/LICENSE", "mit")]);
+        assert_eq!(extract(dir, &zip, &["tool"], 1).unwrap(), ["tool.exe"]);
+    }
+
+    #[test]
+    fn reports_missing_entries() {
+        let Scratch(ref dir) = scratch("missing");
+        let tar = tarball(&[("bin/tool", "#!/bin/sh\n")]);
+        let err = extract(dir, &tar, &["tool", "other", "bin/more"], 0).unwrap_err();
+        assert_eq!(err.to_string(), "Not found in archive: other, bin/more");
+    }
+
+    #[test]
+    fn gunzips_single_executables() {
+        let Scratch(ref dir) = scratch("gz");
+        assert_eq!(extract(dir, &gzip(b"\x7fELF"), &["tool"], 0).unwrap(), ["tool"]);
+        assert_eq!(fs::read(dir.join("out/tool")).unwrap(), b"\x7fELF");
+        assert!(extract(dir, &gzip(b"\x7fELF"), &["a", "b"], 0).is_err());
+    }
+}
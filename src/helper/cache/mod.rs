@@ -0,0 +1,52 @@
+/// Cache of downloads, keyed by their link, that loads can install from
+/// without the network.
+// Extern imports
+use sha2::{Digest, Sha256};
+
+// std imports
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::fetch::{fetch_to_file, FetchError};
+use super::home::DreamerHome;
+
+pub fn downloads_dir(home: &DreamerHome) -> PathBuf {
+    home.cache().join("downloads")
+}
+
+/// The name a download of `url` is cached under.
+pub fn cache_key(url: &str) -> String {
+    Sha256::digest(url.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Where a download of `url` is cached.
+pub fn cached_path(home: &DreamerHome, url: &str) -> PathBuf {
+    downloads_dir(home).join(cache_key(url))
+}
+
+/// Copies the cached download of `url` to `dest`, if there is one.
+pub fn copy_cached(home: &DreamerHome, url: &str, dest: &Path) -> io::Result<Option<u64>> {
+    let cached = cached_path(home, url);
+    if !cached.is_file() {
+        return Ok(None);
+    }
+    fs::copy(cached, dest).map(Some)
+}
+
+/// Downloads `url` into the cache unless it is there already, from `source`
+/// (`url` itself, or a mirror of it). Returns the cached file and whether it
+/// had to be downloaded.
+pub fn warm(home: &DreamerHome, url: &str, source: &str) -> Result<(PathBuf, bool), FetchError> {
+    let cached = cached_path(home, url);
+    if cached.is_file() {
+        return Ok((cached, false));
+    }
+    fs::create_dir_all(downloads_dir(home))?;
+    fetch_to_file(source, &cached)?;
+    Ok((cached, true))
+}
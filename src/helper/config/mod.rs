@@ -0,0 +1,142 @@
+/// The user's own settings, in config.yaml under the zzz home, such as the
+/// mirrors tool links are rewritten to.
+///
+/// ```yaml
+/// mirrors:
+///   - from: https://github.com/
+///     to: https://artifacts.example.com/github/
+///   - from: https://releases.example.org/
+///     to: /srv/mirror/releases/
+/// ```
+///
+/// A relative directory is taken from the zzz home, where config.yaml is.
+// Extern imports
+use serde::Deserialize;
+
+// std imports
+use std::{
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::home::DreamerHome;
+
+/// Downloads of links starting with `from` are made from `to` instead.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Mirror {
+    pub from: String,
+    /// A link prefix, or a local directory (a 'file://' link once read)
+    pub to: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UserConfig {
+    #[serde(default)]
+    pub mirrors: Vec<Mirror>,
+}
+
+impl UserConfig {
+    /// The link to download `url` from: `url` with the prefix of the first
+    /// mirror matching it replaced, or None when no mirror matches.
+    pub fn rewrite(&self, url: &str) -> Option<String> {
+        self.mirrors.iter().find_map(|m| {
+            let rest = url.strip_prefix(m.from.as_str())?;
+            Some(format!("{0}{1}", m.to, rest))
+        })
+    }
+}
+
+/// Whether `path` starts with a windows drive, as in 'C:/' or 'C:\'.
+fn is_drive_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes[2] == b'/' || bytes[2] == b'\\')
+}
+
+/// `to` as a link, turning a local directory into a 'file://' one. A
+/// relative directory is taken from `base`.
+fn mirror_link(to: &str, base: &Path) -> String {
+    if to.contains("://") {
+        return to.to_string();
+    }
+    let path = if Path::new(to).is_absolute() || is_drive_path(to) {
+        PathBuf::from(to)
+    } else {
+        base.join(to)
+    };
+    let path = path.to_string_lossy().replace('\\', "/");
+    match path.starts_with('/') {
+        true => format!("file://{}", path),
+        false => format!("file:///{}", path),
+    }
+}
+
+/// Reads the user config, which is optional.
+pub fn read_user_config(home: &DreamerHome) -> Result<UserConfig, Box<dyn Error>> {
+    let path = home.config();
+    let src = match fs::read_to_string(&path) {
+        Ok(src) => src,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(UserConfig::default()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut config: UserConfig = serde_yaml::from_str(&src)
+        .map_err(|e| format!("Invalid config {0}: {1}", home.display(&path), e))?;
+    for mirror in &mut config.mirrors {
+        mirror.to = mirror_link(&mirror.to, home.root());
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_are_kept() {
+        let base = Path::new("/home/me/.snooze");
+        for to in ["https://proxy.example.com/gh/", "file:///srv/mirror/"] {
+            assert_eq!(mirror_link(to, base), to);
+        }
+    }
+
+    #[test]
+    fn directories_become_file_links() {
+        let base = Path::new("/home/me/.snooze");
+        assert_eq!(mirror_link("/srv/mirror/", base), "file:///srv/mirror/");
+        assert_eq!(mirror_link("C:/mirror/", base), "file:///C:/mirror/");
+        assert_eq!(mirror_link("C:\\mirror\\", base), "file:///C:/mirror/");
+    }
+
+    #[test]
+    fn relative_directories_are_under_base() {
+        let base = Path::new("/home/me/.snooze");
+        assert_eq!(mirror_link("mirror/", base), "file:///home/me/.snooze/mirror/");
+        assert_eq!(mirror_link("./m", base), "file:///home/me/.snooze/./m");
+    }
+
+    #[test]
+    fn first_matching_mirror_wins() {
+        let mirror = |from: &str, to: &str| Mirror {
+            from: from.to_string(),
+            to: to.to_string(),
+        };
+        let config = UserConfig {
+            mirrors: vec![
+                mirror("https://github.com/org/", "file:///org/"),
+                mirror("https://github.com/", "https://proxy/gh/"),
+            ],
+        };
+        assert_eq!(
+            config.rewrite("https://github.com/org/t/a.tgz").as_deref(),
+            Some("file:///org/t/a.tgz")
+        );
+        assert_eq!(
+            config.rewrite("https://github.com/x/a.tgz").as_deref(),
+            Some("https://proxy/gh/x/a.tgz")
+        );
+        assert_eq!(config.rewrite("https://example.com/a"), None);
+    }
+}
@@ -0,0 +1,69 @@
+/// Integrity checks for downloaded tools.
+// Extern imports
+use sha2::{Digest, Sha256, Sha512};
+
+// std imports
+use std::{error::Error, fmt, fs::File, io, path::Path};
+
+use super::Tool;
+
+#[derive(Debug)]
+pub struct DigestMismatch {
+    pub algo: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for DigestMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{0} mismatch: expected {1}, got {2}",
+            self.algo, self.expected, self.actual
+        )
+    }
+}
+
+impl Error for DigestMismatch {}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hash_file<D: Digest + io::Write>(path: &Path) -> io::Result<String> {
+    let mut hasher = D::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    hash_file::<Sha256>(path)
+}
+
+pub fn sha512_file(path: &Path) -> io::Result<String> {
+    hash_file::<Sha512>(path)
+}
+
+fn check(algo: &'static str, expected: &str, actual: String) -> Result<(), DigestMismatch> {
+    if expected.trim().eq_ignore_ascii_case(&actual) {
+        Ok(())
+    } else {
+        Err(DigestMismatch {
+            algo,
+            expected: expected.trim().to_lowercase(),
+            actual,
+        })
+    }
+}
+
+/// Checks `path` against every digest pinned on `tool`.
+/// Tools without any pinned digest always pass.
+pub fn verify_tool(tool: &Tool, path: &Path) -> Result<(), Box<dyn Error>> {
+    if let Some(expected) = &tool.sha256 {
+        check("sha256", expected, sha256_file(path)?)?;
+    }
+    if let Some(expected) = &tool.sha512 {
+        check("sha512", expected, sha512_file(path)?)?;
+    }
+    Ok(())
+}
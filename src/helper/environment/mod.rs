@@ -0,0 +1,268 @@
+/// Environment of task commands: the dreamfile's 'env' maps, the '.env'
+/// file next to it and '${VAR}' expansion of values and command words.
+// Extern imports
+use indexmap::IndexMap;
+
+// std imports
+use std::{env, error::Error, fs, io, path::Path};
+
+/// Variables set for commands, on top of the inherited environment.
+pub type EnvMap = IndexMap<String, String>;
+
+/// Expands '$VAR', '${VAR}' and '${VAR:-default}' in `value`, looking names
+/// up in `vars` first and in the inherited environment after. Unset
+/// variables expand to nothing, and '$$' stands for a literal '$'.
+pub fn expand(value: &str, vars: &EnvMap) -> Result<String, Box<dyn Error>> {
+    let lookup = |name: &str| vars.get(name).cloned().or_else(|| env::var(name).ok());
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            out.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('{') {
+            let end = closing_brace(after)
+                .ok_or_else(|| format!("Unterminated '${{' in '{}'", value))?;
+            let (name, default) = match after[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&after[..end], None),
+            };
+            // Like the shell, ':-' also replaces a variable set to nothing
+            match (lookup(name).filter(|v| !v.is_empty() || default.is_none()), default) {
+                (Some(v), _) => out.push_str(&v),
+                (None, Some(default)) => out.push_str(&expand(default, vars)?),
+                (None, None) => {}
+            }
+            rest = &after[end + 1..];
+        } else {
+            let len = name_len(rest);
+            if len == 0 {
+                out.push('$');
+            } else {
+                out.push_str(&lookup(&rest[..len]).unwrap_or_default());
+                rest = &rest[len..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Index of the '}' closing a '${', skipping over nested ones.
+fn closing_brace(after: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in after.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Length of the variable name at the start of `s`, 0 if there is none.
+fn name_len(s: &str) -> usize {
+    if !s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        return 0;
+    }
+    s.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(s.len())
+}
+
+/// A 'NAME=value' line of a '.env' file.
+struct DotenvEntry {
+    name: String,
+    value: String,
+    /// Whether '${VAR}' in the value is expanded
+    expand: bool,
+}
+
+/// Parses a '.env' file: 'NAME=value' lines, optionally prefixed with
+/// 'export', with '#' comments. Single quoted values are kept as-is, other
+/// values are expanded.
+fn parse_dotenv(src: &str) -> Result<Vec<DotenvEntry>, Box<dyn Error>> {
+    let mut vars = vec![];
+    for (n, line) in src.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((name, value)) = line.split_once('=') else {
+            return Err(format!("line {}: expected NAME=value", n + 1).into());
+        };
+        let name = name.trim();
+        let value = value.trim();
+        let (value, expand) = if let Some(quoted) = value.strip_prefix('\'') {
+            let end = quoted
+                .find('\'')
+                .ok_or_else(|| format!("line {}: unterminated ' quote", n + 1))?;
+            (quoted[..end].to_string(), false)
+        } else if let Some(quoted) = value.strip_prefix('"') {
+            let end = quoted
+                .find('"')
+                .ok_or_else(|| format!("line {}: unterminated \" quote", n + 1))?;
+            let unescaped = quoted[..end].replace("\\n", "\n");
+            (unescaped, true)
+        } else {
+            let value = match value.find(" #") {
+                Some(i) => value[..i].trim_end(),
+                None => value,
+            };
+            (value.to_string(), true)
+        };
+        vars.push(DotenvEntry {
+            name: name.to_string(),
+            value,
+            expand,
+        });
+    }
+    Ok(vars)
+}
+
+/// Reads the '.env' next to the dreamfile at `filepath`. Like other dotenv
+/// loaders, it never overrides a variable that is already set.
+fn load_dotenv(filepath: &str, vars: &mut EnvMap) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(filepath)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(".env");
+    let src = match fs::read_to_string(&path) {
+        Ok(src) => src,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    let parsed = parse_dotenv(&src).map_err(|e| format!("{0}: {1}", path.display(), e))?;
+    for entry in parsed {
+        if env::var_os(&entry.name).is_some() {
+            continue;
+        }
+        let value = if entry.expand {
+            expand(&entry.value, vars)?
+        } else {
+            entry.value
+        };
+        vars.insert(entry.name, value);
+    }
+    Ok(())
+}
+
+/// Adds the entries of `env` to `vars`, in order, expanding each value.
+pub fn apply(vars: &mut EnvMap, env: &EnvMap) -> Result<(), Box<dyn Error>> {
+    for (name, value) in env {
+        let value = expand(value, vars)?;
+        vars.insert(name.clone(), value);
+    }
+    Ok(())
+}
+
+/// The variables every command of the dreamfile at `filepath` gets: its
+/// '.env', then its top level 'env' map.
+pub fn project_env(filepath: &str, env: &EnvMap) -> Result<EnvMap, Box<dyn Error>> {
+    let mut vars = EnvMap::new();
+    load_dotenv(filepath, &mut vars)?;
+    apply(&mut vars, env)?;
+    Ok(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> EnvMap {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn expands_variables() {
+        let vars = vars(&[("A", "1"), ("B_2", "two")]);
+        assert_eq!(expand("${A}-$A-$B_2-${B_2}x", &vars).unwrap(), "1-1-two-twox");
+        assert_eq!(expand("$A.$B_2/", &vars).unwrap(), "1.two/");
+    }
+
+    #[test]
+    fn unset_variables_are_empty() {
+        let vars = EnvMap::new();
+        assert_eq!(expand("[${ZZZ_TEST_UNSET}][$ZZZ_TEST_UNSET]", &vars).unwrap(), "[][]");
+    }
+
+    #[test]
+    fn defaults() {
+        let vars = vars(&[("A", "1"), ("EMPTY", "")]);
+        assert_eq!(expand("${A:-x} ${ZZZ_TEST_UNSET:-x} ${EMPTY:-x}", &vars).unwrap(), "1 x x");
+        assert_eq!(expand("${ZZZ_TEST_UNSET:-}", &vars).unwrap(), "");
+    }
+
+    #[test]
+    fn nested_defaults() {
+        let vars = vars(&[("B", "b")]);
+        assert_eq!(expand("${ZZZ_TEST_UNSET:-${B}}", &vars).unwrap(), "b");
+        assert_eq!(
+            expand("${ZZZ_TEST_UNSET:-${ZZZ_TEST_UNSET:-${B}-c}}!", &vars).unwrap(),
+            "b-c!"
+        );
+    }
+
+    #[test]
+    fn literal_dollars() {
+        let vars = vars(&[("A", "1")]);
+        assert_eq!(expand("$$A $ 5$ $1 $-", &vars).unwrap(), "$A $ 5$ $1 $-");
+    }
+
+    #[test]
+    fn reports_unterminated_braces() {
+        assert!(expand("${A", &EnvMap::new()).is_err());
+        assert!(expand("${A:-${B}", &EnvMap::new()).is_err());
+    }
+
+    #[test]
+    fn inherits_the_environment() {
+        let path = env::var("PATH").unwrap_or_default();
+        assert_eq!(expand("${PATH}", &EnvMap::new()).unwrap(), path);
+        let vars = vars(&[("PATH", "mine")]);
+        assert_eq!(expand("$PATH", &vars).unwrap(), "mine");
+    }
+
+    #[test]
+    fn parses_dotenv_lines() {
+        let src = "# comment\n\nA=1\nexport B = two words # note\nC='${A} kept'\nD=\"${A}\\nx\"\nE=\n";
+        let parsed = parse_dotenv(src).unwrap();
+        let got: Vec<(&str, &str, bool)> = parsed
+            .iter()
+            .map(|e| (e.name.as_str(), e.value.as_str(), e.expand))
+            .collect();
+        assert_eq!(
+            got,
+            [
+                ("A", "1", true),
+                ("B", "two words", true),
+                ("C", "${A} kept", false),
+                ("D", "${A}\nx", true),
+                ("E", "", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_bad_dotenv_lines() {
+        let err = parse_dotenv("A=1\nnot an assignment\n").err().unwrap();
+        assert_eq!(err.to_string(), "line 2: expected NAME=value");
+        assert!(parse_dotenv("A='open\n").is_err());
+        assert!(parse_dotenv("A=\"open\n").is_err());
+    }
+
+    #[test]
+    fn applies_in_order() {
+        let mut vars = vars(&[("A", "1")]);
+        apply(&mut vars, &self::vars(&[("B", "${A}2"), ("A", "${B}3")])).unwrap();
+        assert_eq!(vars["B"], "12");
+        assert_eq!(vars["A"], "123");
+    }
+}
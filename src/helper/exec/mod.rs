@@ -2,25 +2,51 @@
 // Local imports
 use crate::{
     helper::{
-        check_arg_len,
-        colored::Colorize,
+        colored::{Color, Colorize},
         read_file, input_fmt,
-        resource::{calculate_hash, continue_prompt, read_file_gpath},
-        usage_and_quit, verbose_check, verbose_info_print, Tool, ZzzConfig,
+        resource::{continue_prompt, offline_check, parse_tool_spec, read_file_gpath},
+        verbose_check, verbose_info_print, Task, TaskCommand, Tool, ZzzConfig, DEFAULT_TASK,
     },
-    list, LOADCMD,
+    LOADCMD,
 };
 
+use crate::helper::archive;
+use crate::helper::cache::{copy_cached, downloads_dir, warm};
+use crate::helper::config::{read_user_config, UserConfig};
 use crate::helper::errors::*;
+use crate::helper::digest::{sha256_file, verify_tool};
+use crate::helper::environment::{apply, expand, project_env, EnvMap};
+use crate::helper::fetch::{fetch_to_file, set_executable};
+use crate::helper::lockfile::{lock_path, read_lock, write_lock, LockFile, LockedTool};
+use crate::helper::platform::{all_links, resolve_link, ResolvedLink};
+use crate::helper::home::DreamerHome;
+use crate::helper::process::{run_until, Waited};
+use crate::helper::gc::{collect, human_size};
+use crate::helper::project::{known_id, project_id, read_registry, write_registry};
+use crate::helper::shellwords::parse_command;
+use crate::helper::store;
+use crate::helper::state::{clear_state, read_state, write_state, LoadState};
+use crate::helper::tasks::plan;
+use crate::helper::yamledit::{insert_tool, remove_tool, set_tool_version, write_atomic};
 
 // std imports
 use std::{
+    collections::BTreeMap,
     env,
     error::Error,
+    ffi::OsString,
     fs,
     fs::File,
     io::{BufReader, Write},
-    process::Command,
+    iter,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 use super::resource::quit;
@@ -38,20 +64,33 @@ pub fn list_exec(v_file: File, filepath: String, way: usize, global_opts: &[bool
         Ok(config) => match way {
             1 => {
                 infoprint!("'{}' requires the following dependancies:", filepath);
-                let mut num = 1;
-                for tool in config.deps.tools {
-                    println!("\t{0}: {1} \t (from {2})", num, tool.name, tool.link);
-                    num += 1;
+                for (num, tool) in config.deps.tools.iter().enumerate() {
+                    let version = tool.version.as_deref().unwrap_or("-");
+                    match resolve_link(tool) {
+                        Ok(link) => println!(
+                            "\t{0}: {1} \t {2} \t (from {3})",
+                            num + 1,
+                            tool.name,
+                            version,
+                            link.url
+                        ),
+                        Err(e) => {
+                            println!("\t{0}: {1} \t {2} \t ({3})", num + 1, tool.name, version, e)
+                        }
+                    }
                 }
                 Ok(())
             }
 
             _ => {
                 infoprint!("Dependancies for {}:", filepath);
-                let mut num = 1;
-                for tool in config.deps.tools {
-                    println!("\t{0}: {1}", num, tool.name);
-                    num += 1;
+                for (num, tool) in config.deps.tools.iter().enumerate() {
+                    let version = tool.version.as_deref().unwrap_or("-");
+                    println!("\t{0}: {1} \t {2}", num + 1, tool.name, version);
+                }
+                infoprint!("Tasks for {}:", filepath);
+                for (num, (name, task)) in config.r#do.tasks.iter().enumerate() {
+                    println!("\t{0}: {1} \t ({2} commands)", num + 1, name, task.run.len());
                 }
                 Ok(())
             }
@@ -59,27 +98,56 @@ pub fn list_exec(v_file: File, filepath: String, way: usize, global_opts: &[bool
     }
 }
 
-pub fn add_exec(filepath: &String, depname: &String, global_opts: &[bool]) -> Result<(), Box<dyn Error>> {
-    let link = questionprint!("Enter link for '{}':", depname);
+pub fn add_exec(filepath: &String, depname: &str, global_opts: &[bool]) -> Result<(), Box<dyn Error>> {
+    let (name, version) = match parse_tool_spec(depname) {
+        Ok(spec) => spec,
+        Err(e) => {
+            errprint!("{}", e);
+            return Err(e.into());
+        }
+    };
     match read_file_gpath(filepath) {
         Ok(v_file) => {
-            let config: Result<ZzzConfig, serde_yaml::Error> = serde_yaml::from_reader(&v_file.0);
-            let mut conf_f = config.unwrap();
+            let src = fs::read_to_string(&v_file.1)?;
+            let conf_f: ZzzConfig = match serde_yaml::from_str(&src) {
+                Ok(config) => config,
+                Err(..) => {
+                    MISSINGFILEERROR.show_error(&v_file.1, global_opts);
+                    return Err("Invalid Config".into());
+                }
+            };
 
-            let n_tool: Tool = Tool {
-                name: depname.to_string(),
-                link,
+            let edited = match conf_f.deps.tools.iter().find(|t| t.name == name) {
+                // Adding a tool that is already there just bumps its version
+                Some(..) if version.is_some() => {
+                    set_tool_version(&src, &name, version.as_ref().unwrap())
+                }
+                Some(..) => {
+                    errprint!("'{0}' is already in {1}", name, filepath);
+                    return Err("Duplicate tool".into());
+                }
+                None => {
+                    let link = questionprint!("Enter link for '{}' ({{version}} is substituted):", name);
+                    let n_tool: Tool = Tool {
+                        name: name.clone(),
+                        version: version.clone(),
+                        link,
+                        links: BTreeMap::new(),
+                        sha256: None,
+                        sha512: None,
+                        bin: vec![],
+                        strip_components: 0,
+                    };
+                    insert_tool(&src, &n_tool)
+                }
             };
-            let mut tool_to_add: Vec<Tool> = vec![n_tool];
-            //let to_w = conf_f.deps.tools.append(&mut tool_to_add);
-            conf_f.deps.tools.append(&mut tool_to_add);
-            conf_f.project.isloaded = false;
-            let f = std::fs::OpenOptions::new()
-                .write(true)
-                .create(true)
-                .open(v_file.1)
-                .expect("Couldn't open file");
-            serde_yaml::to_writer(f, &conf_f).unwrap();
+            match edited {
+                Ok(edited) => write_atomic(Path::new(&v_file.1), &edited)?,
+                Err(e) => {
+                    errprint!("Cannot edit {0}: {1}", v_file.1, e);
+                    return Err(e);
+                }
+            }
         }
         Err(file) => {
             println!("{}.", file.0);
@@ -87,31 +155,43 @@ pub fn add_exec(filepath: &String, depname: &String, global_opts: &[bool]) -> Re
         }
     };
 
-    successprint!("'{0}' added to {1}", depname, &filepath);
+    match version {
+        Some(version) => successprint!("'{0}' ({1}) added to {2}", name, version, &filepath),
+        None => successprint!("'{0}' added to {1}", name, &filepath),
+    }
 
     Ok(())
 }
 
-pub fn remove_exec(filepath: &String, depname: &String, global_opts: &[bool]) -> Result<(), Box<dyn Error>> {
+pub fn remove_exec(
+    filepath: &String,
+    depname: &String,
+    home_dir: &Result<DreamerHome, String>,
+    global_opts: &[bool],
+) -> Result<(), Box<dyn Error>> {
     match read_file_gpath(filepath) {
         Ok(v_file) => {
-            let config: Result<ZzzConfig, serde_yaml::Error> = serde_yaml::from_reader(&v_file.0);
-            let mut conf_f = config.unwrap();
-            let toollist = &mut conf_f.deps.tools;
-            let index = toollist.iter().position(|x| x.name == *depname).unwrap();
-            warnprint!("This will remove {} from {}", toollist[index].name, filepath);
-            continue_prompt(global_opts);
-            toollist.remove(index);
-            conf_f.project.isloaded = false;
-            let f = std::fs::OpenOptions::new()
-                .write(true)
-                .create(true)
-                .open(v_file.1)
-                .expect("Couldn't open file");
-            serde_yaml::to_writer(f, &conf_f).unwrap();
+            let src = fs::read_to_string(&v_file.1)?;
+            match remove_tool(&src, depname) {
+                Ok(edited) => {
+                    warnprint!("This will remove {} from {}", depname, filepath);
+                    continue_prompt(global_opts);
+                    write_atomic(Path::new(&v_file.1), &edited)?;
+                }
+                Err(e) => {
+                    errprint!("Cannot edit {0}: {1}", v_file.1, e);
+                    return Err(e);
+                }
+            }
+            if let Ok(home) = home_dir {
+                if let Err(e) = uninstall_tool(home, &v_file.1, depname, global_opts) {
+                    warnprint!("'{0}' is still installed ({1})", depname, e);
+                }
+            }
         }
         Err(file) => {
             MISSINGFILEERROR.show_error(&file.1, global_opts);
+            return Err("Missing File".into());
         }
     };
 
@@ -120,14 +200,55 @@ pub fn remove_exec(filepath: &String, depname: &String, global_opts: &[bool]) ->
     Ok(())
 }
 
+/// Drops `name` from the lockfile of the dreamfile at `filepath` and
+/// deletes its executables from the project bin dir.
+fn uninstall_tool(
+    home: &DreamerHome,
+    filepath: &str,
+    name: &str,
+    global_opts: &[bool],
+) -> Result<(), Box<dyn Error>> {
+    let lockpath = lock_path(filepath);
+    let Some(mut lock) = read_lock(&lockpath)? else {
+        return Ok(());
+    };
+    lock.tools.retain(|t| t.name != name);
+    write_lock(&lockpath, &lock)?;
+    if let Some(id) = known_id(home, filepath) {
+        prune_bins(&home.project_bins(&id), &lock, global_opts)?;
+    }
+    Ok(())
+}
+
+/// Deletes whatever in the project bin dir `dir_loc` no tool of `lock`
+/// installs, so the dir holds exactly the locked tools.
+fn prune_bins(dir_loc: &Path, lock: &LockFile, global_opts: &[bool]) -> Result<(), Box<dyn Error>> {
+    let Ok(entries) = fs::read_dir(dir_loc) else {
+        return Ok(());
+    };
+    for entry in entries {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        if name.starts_with('.') || lock.installs(&name) {
+            continue;
+        }
+        if path.is_dir() && !path.is_symlink() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+        verbose_info_print(format!("Removed stale '{}'", name), global_opts);
+    }
+    Ok(())
+}
+
 pub fn load_exec(
     v_file: File,
     filepath: String,
     mut env_cmds: Vec<String>,
-    mut home_dir: Result<String, env::VarError>,
+    home_dir: Result<DreamerHome, String>,
     global_opts: &[bool],
-    argsv: Vec<String>,
-) -> Result<(Vec<String>, u64), Box<dyn Error>> {
+) -> Result<(Vec<String>, String, EnvMap), Box<dyn Error>> {
     let reader: BufReader<File> = BufReader::new(v_file);
     // Parse the YAML into DepConfig struct
     let config: Result<ZzzConfig, serde_yaml::Error> = serde_yaml::from_reader(reader);
@@ -136,144 +257,559 @@ pub fn load_exec(
             INVALIDFILEERR.show_error(&filepath, global_opts);
             Err("Invalid Config".into())
         }
-        Ok(mut config) => {
-            let hashname = calculate_hash(&config.project.name);
-            //println!("{}", hash_string(&config.project.name));
-            if !config.project.isloaded {
-                let _ = list(argsv.clone(), 1, global_opts);
+        Ok(config) => {
+            let env = project_env(&filepath, &config.env)?;
+            let home = home_dir?;
+            let hashname = project_id(&home, &filepath, &config.project.name)?;
+            let lockpath = lock_path(&filepath);
+            let state_current = read_state(&home, &hashname) == Some(LoadState::current(&filepath, &lockpath)?);
+            let lock = read_lock(&lockpath)?;
+            let installed = match (state_current, &lock) {
+                (true, Some(lock)) => installed_tools(&config, lock, &home.project_bins(&hashname)),
+                (true, None) if config.deps.tools.is_empty() => Some(vec![]),
+                _ => None,
+            };
+            if let Some(installed) = installed {
+                for tool in installed {
+                    env_cmds.extend(bin_names(tool));
+                }
+                env_cmds.dedup();
+            } else {
+                let _ = list_exec(File::open(&filepath)?, filepath.clone(), 1, global_opts);
                 if global_opts[2] {
                     infoprint!("This action will download the above, and run any tasks included.");
                 }
                 continue_prompt(global_opts);
                 infoprint!("Getting dependancies from file: '{}'", filepath);
-                for tool in &config.deps.tools {
-                    let _ = tool_install(tool, hashname, &mut env_cmds, &mut home_dir, global_opts);
+                clear_state(&home, &hashname);
+                let old_lock = read_lock(&lockpath)?;
+                let new_lock = resolve_tools(
+                    &config,
+                    old_lock.as_ref(),
+                    &hashname,
+                    &mut env_cmds,
+                    &home,
+                    global_opts,
+                )?;
+                if old_lock.as_ref() != Some(&new_lock) {
+                    write_lock(&lockpath, &new_lock)?;
+                    verbose_info_print(format!("Wrote {}", lockpath.display()), global_opts);
                 }
-                config.project.isloaded = true;
-                let f = std::fs::OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .open(filepath)
-                    .expect("Couldn't open file");
-                serde_yaml::to_writer(f, &config).unwrap();
+                prune_bins(&home.project_bins(&hashname), &new_lock, global_opts)?;
+                write_state(&home, &hashname, &LoadState::current(&filepath, &lockpath)?)?;
             }
-            let result = (env_cmds, hashname);
+            let result = (env_cmds, hashname, env);
             Ok(result)
         }
     }
 }
 
-pub fn load_deps(
-    argsv: Vec<String>,
-    env_cmds: &[String],
-    home_dir: Result<String, env::VarError>,
-    global_opts: &[bool],
-) -> Result<(Vec<String>, u64), Box<dyn Error>> {
-    if check_arg_len(argsv.clone(), 2) {
-        usage_and_quit(LOADCMD.name, "Missing Filename!");
-        return Err("Bad File".into());
+/// The lock entries of the tools of `config` for this platform, if every
+/// one of their executables is still in `dir` and not a dangling link.
+fn installed_tools<'a>(config: &ZzzConfig, lock: &'a LockFile, dir: &Path) -> Option<Vec<&'a LockedTool>> {
+    let mut installed = vec![];
+    for tool in &config.deps.tools {
+        let link = resolve_link(tool).ok()?;
+        let entry = lock.get(&tool.name, link.key.as_deref())?;
+        // exists() follows links, so a link into a deleted store entry fails
+        if !bin_names(entry).iter().all(|b| dir.join(b).exists()) {
+            return None;
+        }
+        installed.push(entry);
+    }
+    Some(installed)
+}
+
+/// The executables a lock entry installs.
+fn bin_names(tool: &LockedTool) -> Vec<String> {
+    if tool.bins.is_empty() {
+        vec![tool.name.clone()]
     } else {
-        let _: Result<(Vec<String>, u64), ()> = match read_file(&argsv, 2, LOADCMD) {
-            Ok(v_file) => {
-                let result = load_exec(
-                    v_file.0,
-                    v_file.1,
-                    env_cmds.to_vec(),
-                    home_dir,
-                    global_opts,
-                    argsv,
-                );
+        tool.bins.clone()
+    }
+}
+
+pub fn lock_exec(
+    v_file: File,
+    filepath: String,
+    home_dir: Result<DreamerHome, String>,
+    update: bool,
+    global_opts: &[bool],
+) -> Result<(), Box<dyn Error>> {
+    let reader: BufReader<File> = BufReader::new(v_file);
+    let config: ZzzConfig = match serde_yaml::from_reader(reader) {
+        Ok(config) => config,
+        Err(_) => {
+            INVALIDFILEERR.show_error(&filepath, global_opts);
+            return Err("Invalid Config".into());
+        }
+    };
+    let home = home_dir?;
+    let hashname = project_id(&home, &filepath, &config.project.name)?;
+    let lockpath = lock_path(&filepath);
+    clear_state(&home, &hashname);
+    let old_lock = if update { None } else { read_lock(&lockpath)? };
+    let new_lock = resolve_tools(
+        &config,
+        old_lock.as_ref(),
+        &hashname,
+        &mut vec![],
+        &home,
+        global_opts,
+    )?;
+    write_lock(&lockpath, &new_lock)?;
+    prune_bins(&home.project_bins(&hashname), &new_lock, global_opts)?;
+    write_state(&home, &hashname, &LoadState::current(&filepath, &lockpath)?)?;
+    successprint!("Locked {0} tools in {1}", config.deps.tools.len(), lockpath.display());
+    Ok(())
+}
 
-                return result;
-                //Ok(result)
+/// Downloads the tools of a dreamfile into the download cache, for loads to
+/// install from later on. Downloads that fail a digest check aren't kept.
+pub fn fetch_exec(
+    v_file: File,
+    filepath: String,
+    home_dir: Result<DreamerHome, String>,
+    all_platforms: bool,
+    global_opts: &[bool],
+) -> Result<(), Box<dyn Error>> {
+    let reader: BufReader<File> = BufReader::new(v_file);
+    let config: ZzzConfig = match serde_yaml::from_reader(reader) {
+        Ok(config) => config,
+        Err(_) => {
+            INVALIDFILEERR.show_error(&filepath, global_opts);
+            return Err("Invalid Config".into());
+        }
+    };
+    if offline_check(global_opts) {
+        errprint!("Cannot fetch while offline");
+        return Err("Offline".into());
+    }
+    let home = home_dir?;
+    let user_config = load_user_config(&home)?;
+    // Known to gc, which then keeps these downloads
+    project_id(&home, &filepath, &config.project.name)?;
+    let lock = read_lock(&lock_path(&filepath))?;
+    let mut failed: Vec<(String, String)> = vec![];
+    let mut fetched = 0;
+    for tool in &config.deps.tools {
+        // The digests of the dreamfile are for the build of this platform
+        let mut urls: Vec<(String, bool)> = vec![];
+        match resolve_link(tool) {
+            Ok(link) => urls.push((link.url, true)),
+            Err(e) if !all_platforms => {
+                failed.push((tool.name.clone(), e.to_string()));
+                continue;
+            }
+            Err(_) => {}
+        }
+        if all_platforms {
+            for url in all_links(tool) {
+                if !urls.iter().any(|(u, _)| *u == url) {
+                    urls.push((url, false));
+                }
+            }
+        }
+        for (url, current) in urls {
+            let source = user_config.rewrite(&url).unwrap_or_else(|| url.clone());
+            verbose_info_print(format!("Fetching {}", source), global_opts);
+            let (cached, downloaded) = match warm(&home, &url, &source) {
+                Ok(warmed) => warmed,
+                Err(e) => {
+                    failed.push((tool.name.clone(), format!("Error grabbing {0}: {1}", source, e)));
+                    continue;
+                }
+            };
+            let locked = lock
+                .as_ref()
+                .and_then(|l| l.tools.iter().find(|t| t.name == tool.name && t.url == url));
+            let checked = match current {
+                true => check_download(tool, locked, &cached),
+                false => check_locked(locked, &cached),
+            };
+            if let Err(e) = checked {
+                let _ = fs::remove_file(&cached);
+                failed.push((tool.name.clone(), e.message));
+                continue;
             }
-            Err(file) => {
-                MISSINGFILEERROR.show_error(&file.1, global_opts);
-                Err(())
+            if downloaded {
+                fetched += 1;
+                infoprint!("Fetched '{0}' ({1})", tool.name, url);
+            } else {
+                verbose_info_print(format!("'{}' is already cached", url), global_opts);
             }
+        }
+    }
+    if !failed.is_empty() {
+        errprint!("Failed to fetch {} downloads:", failed.len());
+        for (name, message) in &failed {
+            eprintln!("\t{0}: {1}", name, message);
+        }
+        return Err("Error fetching tools".into());
+    }
+    successprint!(
+        "Fetched {0} downloads into {1}",
+        fetched,
+        home.display(&downloads_dir(&home))
+    );
+    Ok(())
+}
+
+pub fn gc_exec(home: &DreamerHome, dry_run: bool, global_opts: &[bool]) -> Result<(), Box<dyn Error>> {
+    let mut registry = read_registry(home)?;
+    let found = collect(home, &registry)?;
+    if found.garbage.is_empty() && found.forgotten.is_empty() {
+        successprint!("Nothing to collect in {}", home.display(home.root()));
+        return Ok(());
+    }
+    for dreamfile in &found.forgotten {
+        infoprint!("Forgetting '{}' (dreamfile is gone)", dreamfile);
+    }
+    for item in &found.garbage {
+        println!(
+            "\t{0:>10}  {1} ({2})",
+            human_size(item.size),
+            home.display(&item.path),
+            item.reason
+        );
+    }
+    let total: u64 = found.garbage.iter().map(|g| g.size).sum();
+    infoprint!("{} reclaimable", human_size(total));
+    if dry_run {
+        return Ok(());
+    }
+    continue_prompt(global_opts);
+    let mut freed: u64 = 0;
+    for item in &found.garbage {
+        let removed = if item.path.is_dir() && !item.path.is_symlink() {
+            fs::remove_dir_all(&item.path)
+        } else {
+            fs::remove_file(&item.path)
         };
+        match removed {
+            Ok(()) => freed += item.size,
+            Err(e) => errprint!("Couldn't remove {0}: {1}", home.display(&item.path), e),
+        }
+    }
+    for dreamfile in &found.forgotten {
+        registry.projects.remove(dreamfile);
+    }
+    write_registry(home, &registry)?;
+    successprint!("Freed {}", human_size(freed));
+    Ok(())
+}
+
+/// How many tools are downloaded and installed at the same time.
+const INSTALL_WORKERS: usize = 4;
+
+/// Why a tool couldn't be installed, with what the user can do about it.
+struct InstallError {
+    message: String,
+    help: Option<&'static str>,
+}
+
+impl InstallError {
+    fn new(stage: &str, e: impl std::fmt::Display) -> InstallError {
+        InstallError {
+            message: format!("{0}: {1}", stage, e),
+            help: None,
+        }
     }
+}
+
+fn load_user_config(home: &DreamerHome) -> Result<UserConfig, Box<dyn Error>> {
+    read_user_config(home).inspect_err(|e| errprint!("{}", e))
+}
+
+/// Installs every tool of `config`, pinning each one to its `lock` entry
+/// when there is one, and returns the lockfile describing what was installed.
+/// Tools are installed by a few workers at once; failures are reported
+/// together once every tool has been tried.
+fn resolve_tools(
+    config: &ZzzConfig,
+    lock: Option<&LockFile>,
+    hashname: &str,
+    env_cmds: &mut Vec<String>,
+    home: &DreamerHome,
+    global_opts: &[bool],
+) -> Result<LockFile, Box<dyn Error>> {
+    let tools = &config.deps.tools;
+    let user_config = load_user_config(home)?;
+    let mut failed: Vec<(&str, InstallError)> = vec![];
+    let mut links: Vec<Option<ResolvedLink>> = vec![];
+    for tool in tools {
+        match resolve_link(tool) {
+            Ok(link) => links.push(Some(link)),
+            Err(e) => {
+                failed.push((&tool.name, InstallError::new("Unresolved link", e)));
+                links.push(None);
+            }
+        }
+    }
+    // A lock entry only applies while the dreamfile still points at the same link
+    let pinned = |i: usize, link: &ResolvedLink| {
+        lock.and_then(|l| l.get(&tools[i].name, link.key.as_deref()))
+            .filter(|l| l.url == link.url)
+    };
+    let queue: Vec<usize> = (0..tools.len()).filter(|&i| links[i].is_some()).collect();
+    let next = AtomicUsize::new(0);
+    let mut installed: Vec<Option<LockedTool>> = tools.iter().map(|_| None).collect();
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..INSTALL_WORKERS.min(queue.len()) {
+            let (tx, queue, next, links, user_config) =
+                (tx.clone(), &queue, &next, &links, &user_config);
+            scope.spawn(move || {
+                while let Some(&i) = queue.get(next.fetch_add(1, Ordering::SeqCst)) {
+                    let link = links[i].as_ref().unwrap();
+                    let entry = tool_install(
+                        &tools[i],
+                        link,
+                        pinned(i, link),
+                        hashname,
+                        home,
+                        user_config,
+                        global_opts,
+                    );
+                    let _ = tx.send((i, entry));
+                }
+            });
+        }
+        drop(tx);
+        for (done, (i, entry)) in rx.iter().enumerate() {
+            let progress = format!("[{0}/{1}]", done + 1, queue.len());
+            match entry {
+                Ok(entry) => {
+                    infoprint!("{0} Installed '{1}'", progress, tools[i].name);
+                    installed[i] = Some(entry);
+                }
+                Err(e) => {
+                    errprint!("{0} Failed to install '{1}'", progress, tools[i].name);
+                    failed.push((&tools[i].name, e));
+                }
+            }
+        }
+    });
+    if !failed.is_empty() {
+        errprint!("Failed to install {0} of {1} tools:", failed.len(), tools.len());
+        let mut helps: Vec<&str> = vec![];
+        for (name, e) in &failed {
+            eprintln!("\t{0}: {1}", name, e.message);
+            helps.extend(e.help.filter(|h| !helps.contains(h)));
+        }
+        for help in helps {
+            infoprint!("Help: {}", help);
+        }
+        return Err("Error installing tools".into());
+    }
+
+    let mut new_lock = LockFile::new();
+    for ((tool, link), entry) in tools.iter().zip(&links).zip(installed) {
+        let (Some(link), Some(entry)) = (link, entry) else {
+            continue;
+        };
+        env_cmds.extend(bin_names(&entry));
+        new_lock.tools.push(entry);
+        // Keep what teammates on other platforms resolved for this tool
+        if let Some(l) = lock {
+            new_lock.tools.extend(
+                l.tools
+                    .iter()
+                    .filter(|t| t.name == tool.name && t.platform != link.key)
+                    .filter(|t| t.platform.as_ref().is_some_and(|p| tool.links.contains_key(p)))
+                    .cloned(),
+            );
+        }
+    }
+    Ok(new_lock)
+}
+
+pub fn load_deps(
+    argsv: Vec<String>,
+    env_cmds: &[String],
+    home_dir: Result<DreamerHome, String>,
+    global_opts: &[bool],
+) -> Result<(Vec<String>, String, EnvMap), Box<dyn Error>> {
+    let _: Result<(Vec<String>, String, EnvMap), ()> = match read_file(&argsv, 2, LOADCMD) {
+        Ok(v_file) => {
+            let result = load_exec(
+                v_file.0,
+                v_file.1,
+                env_cmds.to_vec(),
+                home_dir,
+                global_opts,
+            );
+
+            return result;
+            //Ok(result)
+        }
+        Err(file) => {
+            MISSINGFILEERROR.show_error(&file.1, global_opts);
+            Err(())
+        }
+    };
     Err("Bad File".into())
 }
 
 fn tool_install(
     tool: &Tool,
-    hashname: u64,
-    env_cmds: &mut Vec<String>,
-    home_dir: &mut Result<String, env::VarError>,
+    link: &ResolvedLink,
+    locked: Option<&LockedTool>,
+    hashname: &str,
+    home: &DreamerHome,
+    user_config: &UserConfig,
     global_opts: &[bool],
-) -> Result<(), Box<dyn Error>> {
-    env_cmds.push(tool.name.clone());
+) -> Result<LockedTool, InstallError> {
+    let dir_loc = home.project_bins(hashname);
+    fs::create_dir_all(&dir_loc).map_err(|e| InstallError::new("Error creating dir", e))?;
+    // A pinned download that is already in the store only needs linking, as
+    // long as the dreamfile's digests are known to hold for it: a sha512 pin
+    // can only be checked on the download itself.
+    if let Some(l) = locked {
+        let bins = bin_names(l);
+        let verified = tool.sha512.is_none()
+            && tool
+                .sha256
+                .as_ref()
+                .is_none_or(|s| s.eq_ignore_ascii_case(&l.sha256));
+        if verified
+            && bins.len() == tool.bin.len().max(1)
+            && store::contains(home, &l.sha256, &bins)
+        {
+            verbose_info_print(format!("'{}' is already in the store", tool.name), global_opts);
+            link_bins(&store::artifact_dir(home, &l.sha256), &dir_loc, &bins)?;
+            return Ok(l.clone());
+        }
+    }
+
+    // The lockfile keeps the dreamfile's link, only the download goes to a mirror
+    let source = user_config.rewrite(&link.url).unwrap_or_else(|| link.url.clone());
     verbose_info_print(
-        format!("Installing {0} from {1}", tool.name, tool.link),
+        format!("Installing {0} from {1}", tool.name, source),
         global_opts,
     );
-    let link = &tool.link;
-    let link_str = link.to_string();
-    if cfg!(windows) {
-        let dir_loc = format!(
-            "{0}\\.snooze\\bins\\{1}\\",
-            home_dir.as_mut().unwrap(),
-            hashname
-        );
-        match fs::create_dir_all(&dir_loc) {
-            Ok(..) => {
-                let namef = format!("{0}{1}", dir_loc, tool.name);
-                let args: Vec<&str> = vec!["/C", "curl", &link_str, "--output", &namef, "--silent"];
-                //println!("{:?}", args);
-
-                let status = Command::new("cmd").args(args).status()?;
-                if status.success() {
-                    let args2: Vec<&str> = vec!["/C", "chmod", "a+x", &namef];
-                    let status2 = Command::new("cmd").args(args2).status()?;
-                    if status2.success() {
-                        verbose_info_print(format!("'{}' installed", tool.name), global_opts);
-                        Ok(())
-                    } else {
-                        errprint!("Error grabbing: '{}'", tool.name);
-                        continue_prompt(global_opts);
-                        Err("Error grabbing".into())
-                    }
-                    //infoprint!("Command '{}' executed successfully", command);
-                } else {
-                    errprint!("Error grabbing: '{}'", tool.name);
-                    continue_prompt(global_opts);
-                    Err("Error grabbing".into())
-                }
-            }
-            Err(..) => {
-                errprint!("Error creating dir");
-                Err("Error creating dir".into())
-            }
+    let staging =
+        store::staging_dir(home, &tool.name).map_err(|e| InstallError::new("Error creating dir", e))?;
+    let installed = download_into(tool, link, &source, locked, &staging, home, global_opts);
+    let (sha256, size, bins) = match installed {
+        Ok(installed) => installed,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(e);
         }
-    } else {
-        let dir_loc = format!("{0}/.snooze/bins/{1}/", home_dir.as_mut().unwrap(), hashname);
-        match fs::create_dir_all(&dir_loc) {
-            Ok(..) => {
-                let link_str_f = link_str.to_string();
-                let namef = format!("{0}{1}", dir_loc, tool.name);
-                let args: Vec<&str> = vec!["-c", "/usr/bin/curl", &link_str_f, "--output", &namef];
-                let status = Command::new("bash").args(args).status()?;
-
-                if status.success() {
-                    let args2: Vec<&str> = vec!["-c", "chmod", "a+x", &namef];
-                    let status2 = Command::new("bash").args(args2).status()?;
-                    if status2.success() {
-                        verbose_info_print(format!("'{}' installed", tool.name), global_opts);
-                        Ok(())
-                    } else {
-                        errprint!("Error grabbing: '{}'", tool.name);
-                        Err("Error grabbing".into())
-                    }
-                } else {
-                    errprint!("Error grabbing: '{}'", tool.name);
-                    Err("Error grabbing".into())
-                }
-            }
-            Err(..) => {
-                errprint!("Error creating dir");
-                Err("Error creating dir".into())
-            }
+    };
+    let artifact = store::commit(home, &sha256, &staging, &bins)
+        .map_err(|e| InstallError::new("Error storing", e))?;
+    link_bins(&artifact, &dir_loc, &bins)?;
+    verbose_info_print(format!("'{}' installed", tool.name), global_opts);
+    Ok(LockedTool {
+        name: tool.name.clone(),
+        platform: link.key.clone(),
+        url: link.url.clone(),
+        sha256,
+        size,
+        bins: if bins == [tool.name.clone()] { vec![] } else { bins },
+    })
+}
+
+/// Downloads `link` from `source`, verifies it and unpacks its executables
+/// into `staging`. Returns the digest and size of the download and the
+/// executables' names. The download cache is used when it has the link, and
+/// is all there is offline.
+fn download_into(
+    tool: &Tool,
+    link: &ResolvedLink,
+    source: &str,
+    locked: Option<&LockedTool>,
+    staging: &Path,
+    home: &DreamerHome,
+    global_opts: &[bool],
+) -> Result<(String, u64, Vec<String>), InstallError> {
+    let download = staging.join(format!(".{}.download", tool.name));
+    let cached = copy_cached(home, &link.url, &download)
+        .map_err(|e| InstallError::new("Error reading the download cache", e))?;
+    let size = match cached {
+        Some(size) => size,
+        None if offline_check(global_opts) => {
+            return Err(InstallError {
+                message: format!("Not in the download cache, and offline ({})", link.url),
+                help: Some("Run 'zzz fetch <filename>' while online to fill the download cache."),
+            })
+        }
+        None => fetch_to_file(source, &download).map_err(|e| InstallError::new("Error grabbing", e))?,
+    };
+    let sha256 = check_download(tool, locked, &download)?;
+    let bins = unpack_download(tool, &download, staging)
+        .map_err(|e| InstallError::new("Error unpacking", e))?;
+    let _ = fs::remove_file(&download);
+    for bin in &bins {
+        set_executable(&staging.join(bin)).map_err(|e| InstallError::new("Error grabbing", e))?;
+    }
+    Ok((sha256, size, bins))
+}
+
+/// Links each of `bins` from the store's `artifact` dir into `dir_loc`.
+fn link_bins(artifact: &Path, dir_loc: &Path, bins: &[String]) -> Result<(), InstallError> {
+    for bin in bins {
+        store::link(&artifact.join(bin), &dir_loc.join(bin))
+            .map_err(|e| InstallError::new("Error linking", e))?;
+    }
+    Ok(())
+}
+
+/// Verifies a download against the digests pinned in the dreamfile and the
+/// lockfile, returning its sha256.
+fn check_download(
+    tool: &Tool,
+    locked: Option<&LockedTool>,
+    download: &Path,
+) -> Result<String, InstallError> {
+    verify_tool(tool, download).map_err(|e| InstallError::new("Refusing to install", e))?;
+    check_locked(locked, download)
+}
+
+/// Verifies a download against its lockfile entry, returning its sha256.
+fn check_locked(locked: Option<&LockedTool>, download: &Path) -> Result<String, InstallError> {
+    let sha256 = sha256_file(download).map_err(|e| InstallError::new("Error hashing", e))?;
+    if let Some(l) = locked {
+        if !l.sha256.eq_ignore_ascii_case(&sha256) {
+            return Err(InstallError {
+                message: format!(
+                    "Refusing to install: does not match the lockfile (expected {0}, got {1})",
+                    l.sha256, sha256
+                ),
+                help: Some("Run 'zzz lock <filename> --update' to re-resolve it."),
+            });
+        }
+    }
+    Ok(sha256)
+}
+
+/// Moves a verified download into `dir_loc`, extracting it first if it is an
+/// archive. Returns the file names of the installed executables.
+fn unpack_download(
+    tool: &Tool,
+    download: &Path,
+    dir_loc: &Path,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    match archive::detect(download)? {
+        Some(kind) => {
+            let specs = if tool.bin.is_empty() {
+                vec![tool.name.clone()]
+            } else {
+                tool.bin.clone()
+            };
+            let written =
+                archive::extract_bins(download, kind, &specs, tool.strip_components, dir_loc)?;
+            Ok(written
+                .iter()
+                .filter_map(|p| p.file_name())
+                .map(|f| f.to_string_lossy().to_string())
+                .collect())
+        }
+        None => {
+            fs::rename(download, dir_loc.join(&tool.name))?;
+            Ok(vec![tool.name.clone()])
         }
     }
 }
@@ -281,8 +817,11 @@ fn tool_install(
 pub fn run_exec(
     v_file: File,
     filepath: String,
+    task: Option<String>,
+    jobs: Option<usize>,
+    home_dir: Result<DreamerHome, String>,
     global_opts: Vec<bool>,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<i32, Box<dyn Error>> {
     let reader: BufReader<File> = BufReader::new(v_file);
     // Parse the YAML into PluConfig struct
     let config: Result<ZzzConfig, serde_yaml::Error> = serde_yaml::from_reader(reader);
@@ -293,33 +832,375 @@ pub fn run_exec(
         }
 
         Ok(config) => {
-            let mut okcount: i32 = 0;
-            let mut cmdcount: i32 = 0;
-            // Execute commands in the 'run' section
-            infoprint!("Running '{}': \n", filepath);
-            for command in config.r#do.run {
-                cmdcount += 1;
-                let mut parts = command.split_whitespace();
-                let program = parts.next().ok_or("Missing command")?;
-                let args: Vec<&str> = parts.collect();
-                let status = Command::new(program).args(args).status()?;
-                if status.success() {
-                    if verbose_check(&global_opts) {
-                        infoprint!("Command '{}' executed successfully", command);
+            let task_name = task.unwrap_or(DEFAULT_TASK.to_string());
+            if !config.r#do.tasks.contains_key(&task_name) {
+                errprint!("No task '{0}' in {1}", task_name, filepath);
+                let names: Vec<&str> = config.r#do.tasks.keys().map(String::as_str).collect();
+                infoprint!("Available tasks: {}", names.join(", "));
+                return Err("Missing Task".into());
+            }
+            let order = match plan(&config.r#do.tasks, &task_name) {
+                Ok(order) => order,
+                Err(e) => {
+                    errprint!("{}", e);
+                    return Err(e);
+                }
+            };
+            let bins = match tools_dir(&home_dir, &filepath, &config.project.name) {
+                Ok(bins) => Some(bins),
+                Err(e) => {
+                    warnprint!("Project tools won't be on PATH ({})", e);
+                    None
+                }
+            };
+            let envs = project_env(&filepath, &config.env).and_then(|base| {
+                order
+                    .iter()
+                    .map(|name| task_env(&base, &config.r#do.tasks[name], bins.as_deref()))
+                    .collect::<Result<Vec<EnvMap>, _>>()
+            });
+            let envs = match envs {
+                Ok(envs) => envs,
+                Err(e) => {
+                    errprint!("Invalid env: {}", e);
+                    return Err(e);
+                }
+            };
+            // Commands run next to the dreamfile unless told otherwise
+            let base_dir = Path::new(&filepath)
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            let tasks: Vec<&Task> = order.iter().map(|n| &config.r#do.tasks[n]).collect();
+            let deps: Vec<Vec<usize>> = tasks
+                .iter()
+                .map(|t| {
+                    t.depends_on
+                        .iter()
+                        .map(|d| order.iter().position(|n| n == d).unwrap())
+                        .collect()
+                })
+                .collect();
+            // Independent tasks only overlap with '-j', the commands of a
+            // task only with 'parallel: true', by default one per CPU
+            let dag_parallel = jobs.is_some();
+            let limit = jobs
+                .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+                .max(1);
+            let concurrent = limit > 1 && (dag_parallel || tasks.iter().any(|t| t.parallel));
+            let prefixes: Vec<Option<String>> = order
+                .iter()
+                .enumerate()
+                .map(|(i, name)| concurrent.then(|| task_prefix(name, i, &order)))
+                .collect();
+
+            let mut progress: Vec<TaskProgress> = tasks.iter().map(|_| TaskProgress::default()).collect();
+            let mut failures: Vec<CommandFailure> = vec![];
+            let mut status: i32 = 0;
+            let mut running: usize = 0;
+            let (tx, rx) = mpsc::channel();
+            // Start every command that can run, dependencies first, until a
+            // failure that isn't allowed to happen
+            loop {
+                while status == 0 && running < limit {
+                    let Some(t) = next_startable(&tasks, &deps, &progress, dag_parallel) else {
+                        break;
+                    };
+                    let (task, p) = (tasks[t], &mut progress[t]);
+                    if !p.started {
+                        infoprint!("Running '{0}' from '{1}': \n", order[t], filepath);
+                        p.started = true;
+                        p.deadline = task.timeout.map(|s| Instant::now() + Duration::from_secs(s));
+                        continue;
+                    }
+                    let i = p.next;
+                    p.next += 1;
+                    let command = &task.run[i];
+                    let cwd = match command.options.cwd.as_ref().or(task.cwd.as_ref()) {
+                        Some(cwd) => base_dir.join(cwd),
+                        None => base_dir.to_path_buf(),
+                    };
+                    let command_deadline = command
+                        .options
+                        .timeout
+                        .map(|s| Instant::now() + Duration::from_secs(s));
+                    let deadline = [p.deadline, command_deadline].into_iter().flatten().min();
+                    match build_command(command, &envs[t], &cwd) {
+                        Ok(mut c) => {
+                            running += 1;
+                            p.running += 1;
+                            let tx = tx.clone();
+                            let prefix = prefixes[t].clone();
+                            thread::spawn(move || {
+                                let waited = run_until(&mut c, deadline, prefix.as_deref());
+                                let _ = tx.send((t, i, waited));
+                            });
+                        }
+                        Err(e) => {
+                            let failure = settle(&order[t], task, command, Err(e), &global_opts);
+                            status = record(failure, &mut failures);
+                        }
                     }
-                    okcount += 1;
-                } else {
-                    errprint!("Error executing command: '{}'", command);
+                }
+                if running == 0 {
+                    break;
+                }
+                let (t, i, waited) = rx.recv()?;
+                running -= 1;
+                progress[t].running -= 1;
+                let waited = waited.map_err(Box::<dyn Error>::from);
+                let failure = settle(&order[t], tasks[t], &tasks[t].run[i], waited, &global_opts);
+                let code = record(failure, &mut failures);
+                if status == 0 {
+                    status = code;
                 }
             }
-            if cmdcount == okcount {
-                println!();
+            println!();
+            if failures.is_empty() {
                 successprint!("All tasks completed successfully");
-                println!();
+            } else {
+                print_failures(&failures);
+            }
+            println!();
+            Ok(status)
+        }
+    }
+}
+
+/// Where a task of the plan is at.
+#[derive(Default)]
+struct TaskProgress {
+    started: bool,
+    /// Index of the next command to start
+    next: usize,
+    /// Commands started and not done yet
+    running: usize,
+    deadline: Option<Instant>,
+}
+
+impl TaskProgress {
+    fn finished(&self, task: &Task) -> bool {
+        self.started && self.next == task.run.len() && self.running == 0
+    }
+}
+
+/// The task (by its index in the plan) having a command, or its start, to
+/// run next. Tasks start once their dependencies are finished, and only
+/// while no other task is going unless `dag_parallel`.
+fn next_startable(
+    tasks: &[&Task],
+    deps: &[Vec<usize>],
+    progress: &[TaskProgress],
+    dag_parallel: bool,
+) -> Option<usize> {
+    let active = |t: usize| progress[t].started && !progress[t].finished(tasks[t]);
+    for (t, task) in tasks.iter().enumerate() {
+        let p = &progress[t];
+        if !p.started {
+            if deps[t].iter().all(|&d| progress[d].finished(tasks[d]))
+                && (dag_parallel || !(0..tasks.len()).any(active))
+            {
+                return Some(t);
             }
-            Ok(())
+        } else if p.next < task.run.len() && (task.parallel || p.running == 0) {
+            return Some(t);
+        }
+    }
+    None
+}
+
+/// The coloured, aligned 'name |' shown before the output of a task's
+/// commands when several of them run at once.
+fn task_prefix(name: &str, index: usize, order: &[String]) -> String {
+    const COLORS: [Color; 6] = [
+        Color::Cyan,
+        Color::Magenta,
+        Color::Yellow,
+        Color::Blue,
+        Color::Green,
+        Color::BrightRed,
+    ];
+    let width = order.iter().map(|n| n.len()).max().unwrap_or(0);
+    format!("    {0:>1$} |", name, width)
+        .color(COLORS[index % COLORS.len()])
+        .bold()
+        .to_string()
+}
+
+/// Reports how a command ended, returning its failure if it had one.
+fn settle(
+    task_name: &str,
+    task: &Task,
+    command: &TaskCommand,
+    ended: Result<Waited, Box<dyn Error>>,
+    global_opts: &[bool],
+) -> Option<CommandFailure> {
+    let mut timed_out = false;
+    let code = match ended {
+        Ok(Waited::Exited(exit)) => exit_code(exit),
+        Ok(Waited::TimedOut) => {
+            timed_out = true;
+            TIMEOUT_STATUS
+        }
+        Err(e) => {
+            errprint!("Error executing command: '{0}' ({1})", command.cmd, e);
+            spawn_error_code(e.as_ref())
+        }
+    };
+    if code == 0 {
+        if verbose_check(global_opts) {
+            infoprint!("Command '{}' executed successfully", command.cmd);
+        }
+        return None;
+    }
+    let ignored = command
+        .options
+        .continue_on_error
+        .unwrap_or(task.continue_on_error);
+    let what = if timed_out {
+        "timed out".to_string()
+    } else {
+        format!("failed with status {}", code)
+    };
+    if ignored {
+        warnprint!("Command '{0}' {1}, continuing", command.cmd, what);
+    } else {
+        errprint!("Command '{0}' {1}", command.cmd, what);
+    }
+    Some(CommandFailure {
+        task: task_name.to_string(),
+        cmd: command.cmd.clone(),
+        code,
+        timed_out,
+        ignored,
+    })
+}
+
+/// Adds `failure` to `failures`, returning the status the run must stop
+/// with because of it (0 to keep going).
+fn record(failure: Option<CommandFailure>, failures: &mut Vec<CommandFailure>) -> i32 {
+    match failure {
+        Some(failure) => {
+            let status = if failure.ignored { 0 } else { failure.code };
+            failures.push(failure);
+            status
+        }
+        None => 0,
+    }
+}
+
+/// Exit status of a command killed for running past its timeout, the same
+/// as coreutils' 'timeout' uses.
+const TIMEOUT_STATUS: i32 = 124;
+
+/// A command of a task that exited unsuccessfully.
+struct CommandFailure {
+    task: String,
+    cmd: String,
+    code: i32,
+    timed_out: bool,
+    ignored: bool,
+}
+
+fn print_failures(failures: &[CommandFailure]) {
+    if failures.iter().all(|f| f.ignored) {
+        warnprint!("{} command(s) failed:", failures.len());
+    } else {
+        errprint!("{} command(s) failed:", failures.len());
+    }
+    for failure in failures {
+        let what = if failure.timed_out {
+            "timed out".to_string()
+        } else {
+            format!("status {}", failure.code)
+        };
+        let note = if failure.ignored { ", ignored" } else { "" };
+        eprintln!("\t{0}: '{1}' ({2}{3})", failure.task, failure.cmd, what, note);
+    }
+}
+
+/// The status a shell would report for `exit`: its code, or 128 plus the
+/// signal that killed it.
+fn exit_code(exit: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = exit.signal() {
+            return 128 + signal;
         }
     }
+    exit.code().unwrap_or(1)
+}
+
+/// The status a shell would report for a command that couldn't be started.
+fn spawn_error_code(e: &(dyn Error + 'static)) -> i32 {
+    match e.downcast_ref::<std::io::Error>().map(|e| e.kind()) {
+        Some(std::io::ErrorKind::NotFound) => 127,
+        Some(_) => 126,
+        None => 2,
+    }
+}
+
+/// Where the tools of the dreamfile at `filepath` are installed.
+fn tools_dir(
+    home_dir: &Result<DreamerHome, String>,
+    filepath: &str,
+    name: &str,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let home = home_dir.as_ref().map_err(|e| e.clone())?;
+    Ok(home.project_bins(&project_id(home, filepath, name)?))
+}
+
+/// The variables the commands of `task` run with: the project's, the
+/// task's own, and PATH with the project's tools first.
+fn task_env(base: &EnvMap, task: &Task, bins: Option<&Path>) -> Result<EnvMap, Box<dyn Error>> {
+    let mut vars = base.clone();
+    apply(&mut vars, &task.env)?;
+    if let Some(bins) = bins {
+        let inherited = match vars.get("PATH") {
+            Some(path) => OsString::from(path),
+            None => env::var_os("PATH").unwrap_or_default(),
+        };
+        let path = env::join_paths(iter::once(bins.to_path_buf()).chain(env::split_paths(&inherited)))?;
+        vars.insert("PATH".to_string(), path.to_string_lossy().to_string());
+    }
+    Ok(vars)
+}
+
+/// Turns a task command into a process: through the shell when asked to,
+/// otherwise split into words with its leading 'NAME=value' set as env.
+/// Programs are looked up in the PATH of `env`, when it sets one.
+fn build_command(command: &TaskCommand, env: &EnvMap, cwd: &Path) -> Result<Command, Box<dyn Error>> {
+    if !cwd.is_dir() {
+        return Err(format!("No directory '{}'", cwd.display()).into());
+    }
+    if command.shell {
+        let mut c = if cfg!(windows) {
+            Command::new("cmd")
+        } else {
+            Command::new("/bin/sh")
+        };
+        c.arg(if cfg!(windows) { "/C" } else { "-c" })
+            .arg(&command.cmd)
+            .envs(env)
+            .current_dir(cwd);
+        return Ok(c);
+    }
+    // Words are expanded like the shell would, but never split again
+    let parsed = parse_command(&command.cmd)?;
+    let argv = parsed
+        .argv
+        .iter()
+        .map(|w| expand(w, env))
+        .collect::<Result<Vec<String>, _>>()?;
+    let mut assigned = EnvMap::new();
+    for (name, value) in &parsed.env {
+        assigned.insert(name.clone(), expand(value, env)?);
+    }
+    let (program, args) = argv.split_first().ok_or("Missing command")?;
+    let mut c = Command::new(program);
+    c.args(args).envs(env).envs(assigned).current_dir(cwd);
+    Ok(c)
 }
 
 pub fn createfile(ufile_name: String) -> Result<std::string::String, std::string::String> {
@@ -331,7 +1212,6 @@ pub fn createfile(ufile_name: String) -> Result<std::string::String, std::string
   name: \"\",
   description: \"\",
   version: \"0.0.0\",
-  isloaded: false,
 }
 
 do:
@@ -348,7 +1228,7 @@ deps:
 
 pub fn extension_exec(
     argsv: Vec<String>,
-    home_dir: Result<String, env::VarError>,
+    home_dir: Result<DreamerHome, String>,
     global_opts: &[bool],
 ) {
     let mut to_exec: String;
@@ -363,12 +1243,19 @@ pub fn extension_exec(
         },
         _ => {ext_args = argsv.clone().drain(3..*argslen).collect();}
     }
-    if cfg!(windows) {
-        to_exec = format!("{}/.snooze/ext/{}.exe", home_dir.unwrap(), &argsv[2]).to_owned();
-        let f_fexec = str::replace(&to_exec, "\\", "/").to_owned();
-        to_exec = f_fexec.to_owned();
+    let ext_name = if cfg!(windows) {
+        format!("{}.exe", &argsv[2])
     } else {
-        to_exec = argsv[2].to_owned();
+        argsv[2].to_owned()
+    };
+    // Extensions in the zzz home come first, then the ones on PATH
+    match home_dir.map(|home| home.ext().join(&ext_name)) {
+        Ok(ext) if ext.is_file() => {
+            to_exec = ext.to_string_lossy().to_string();
+            let f_fexec = str::replace(&to_exec, "\\", "/").to_owned();
+            to_exec = f_fexec.to_owned();
+        }
+        _ => to_exec = ext_name,
     }
     verbose_info_print(format!("Executing {}", to_exec).to_string(), global_opts);
     //println!("{}", to_exec);
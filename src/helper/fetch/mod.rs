@@ -0,0 +1,119 @@
+/// In-process downloading of tool links.
+// std imports
+use std::{
+    error::Error,
+    fmt, fs,
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const MAX_REDIRECTS: u32 = 10;
+/// How long a server may go without sending anything before the download
+/// is given up on.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum FetchError {
+    /// The server answered, but with a non-success HTTP status.
+    Status(u16, String),
+    /// The request never got a proper answer (DNS, TLS, connection...).
+    Transport(String),
+    /// The link uses a scheme we don't know how to fetch.
+    Scheme(String),
+    /// A 'file://' link whose file can't be opened.
+    File(String, io::Error),
+    Io(io::Error),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Status(code, link) => write!(f, "HTTP {} from '{}'", code, link),
+            FetchError::Transport(msg) => write!(f, "{}", msg),
+            FetchError::Scheme(link) => write!(f, "Unsupported link '{}'", link),
+            FetchError::File(link, e) => write!(f, "Cannot open '{0}': {1}", link, e),
+            FetchError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for FetchError {}
+
+impl From<io::Error> for FetchError {
+    fn from(e: io::Error) -> Self {
+        FetchError::Io(e)
+    }
+}
+
+fn file_link_path(link: &str) -> Option<PathBuf> {
+    let rest = link.strip_prefix("file://")?;
+    // 'file:///C:/x' on windows, 'file:///x' everywhere else
+    let rest = match rest.strip_prefix('/') {
+        Some(r) if cfg!(windows) && r.chars().nth(1) == Some(':') => r,
+        _ => rest,
+    };
+    Some(PathBuf::from(rest))
+}
+
+fn open_link(link: &str) -> Result<Box<dyn Read + Send + Sync>, FetchError> {
+    if let Some(path) = file_link_path(link) {
+        let file = File::open(path).map_err(|e| FetchError::File(link.to_string(), e))?;
+        return Ok(Box::new(file));
+    }
+    if !(link.starts_with("http://") || link.starts_with("https://")) {
+        return Err(FetchError::Scheme(link.to_string()));
+    }
+    let agent = ureq::AgentBuilder::new()
+        .redirects(MAX_REDIRECTS)
+        .try_proxy_from_env(true)
+        .timeout_connect(Duration::from_secs(30))
+        .timeout_read(READ_TIMEOUT)
+        .user_agent(concat!("zzz/", env!("CARGO_PKG_VERSION")))
+        .build();
+    match agent.get(link).call() {
+        Ok(resp) => Ok(Box::new(resp.into_reader())),
+        Err(ureq::Error::Status(code, _)) => Err(FetchError::Status(code, link.to_string())),
+        Err(ureq::Error::Transport(t)) => Err(FetchError::Transport(t.to_string())),
+    }
+}
+
+/// Downloads `link` into `dest`, returning the number of bytes written.
+/// The body is streamed into a sibling '.part' file which is only renamed
+/// over `dest` once the transfer completed.
+pub fn fetch_to_file(link: &str, dest: &Path) -> Result<u64, FetchError> {
+    let mut reader = open_link(link)?;
+    let mut part = dest.as_os_str().to_owned();
+    part.push(".part");
+    let part = PathBuf::from(part);
+    let written = File::create(&part).and_then(|mut f| {
+        let n = io::copy(&mut reader, &mut f)?;
+        f.flush()?;
+        Ok(n)
+    });
+    match written {
+        Ok(n) => {
+            fs::rename(&part, dest)?;
+            Ok(n)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&part);
+            Err(e.into())
+        }
+    }
+}
+
+/// Marks `path` as executable for everyone (a+x). No-op on windows.
+pub fn set_executable(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = fs::metadata(path)?.permissions();
+        perms.set_mode(perms.mode() | 0o111);
+        fs::set_permissions(path, perms)?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
@@ -0,0 +1,182 @@
+/// Finding what in the zzz home no known project uses anymore.
+// std imports
+use std::{
+    collections::BTreeSet,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use super::cache::{cache_key, downloads_dir};
+use super::home::DreamerHome;
+use super::lockfile::{lock_path, read_lock};
+use super::platform::all_links;
+use super::project::Registry;
+use super::ZzzConfig;
+
+/// Staging dirs of the store older than this are left over from a load
+/// that didn't finish.
+const STALE_STAGING: Duration = Duration::from_secs(60 * 60);
+
+/// Something that can be deleted, and why.
+pub struct Garbage {
+    pub path: PathBuf,
+    pub size: u64,
+    pub reason: String,
+}
+
+/// What a collection would do.
+pub struct Collection {
+    pub garbage: Vec<Garbage>,
+    /// Registry entries whose dreamfile is gone
+    pub forgotten: Vec<String>,
+}
+
+/// Size of `path` on disk, without following symlinks.
+pub fn disk_size(path: &Path) -> u64 {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|e| disk_size(&e.path())).sum())
+        .unwrap_or(0)
+}
+
+/// '1536' -> '1.5 KiB'
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{0:.1} {1}", size, UNITS[unit])
+    }
+}
+
+fn entries(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|e| e.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+fn garbage(path: PathBuf, reason: impl Into<String>) -> Garbage {
+    Garbage {
+        size: disk_size(&path),
+        path,
+        reason: reason.into(),
+    }
+}
+
+/// The links of every tool of the dreamfile at `path`, for all platforms.
+fn dreamfile_links(path: &str) -> Vec<String> {
+    let config: Option<ZzzConfig> = fs::read_to_string(path)
+        .ok()
+        .and_then(|src| serde_yaml::from_str(&src).ok());
+    config
+        .map(|c| c.deps.tools.iter().flat_map(all_links).collect())
+        .unwrap_or_default()
+}
+
+/// Works out what can go: bin dirs of projects whose dreamfile is gone (or
+/// that no dreamfile ever claimed), files of live bin dirs their lockfile
+/// doesn't name, store entries nothing links to, and cached downloads of
+/// links no live dreamfile or lockfile has.
+pub fn collect(home: &DreamerHome, registry: &Registry) -> Result<Collection, Box<dyn Error>> {
+    let mut found = vec![];
+    let mut forgotten = vec![];
+    let mut live: Vec<(&str, PathBuf)> = vec![];
+    let mut wanted_downloads: BTreeSet<String> = BTreeSet::new();
+    for (dreamfile, entry) in &registry.projects {
+        if Path::new(dreamfile).is_file() {
+            wanted_downloads.extend(dreamfile_links(dreamfile).iter().map(|l| cache_key(l)));
+            live.push((&entry.id, lock_path(dreamfile)));
+        } else {
+            forgotten.push(dreamfile.clone());
+            let state = home.state().join(format!("{}.yaml", entry.id));
+            if state.exists() {
+                found.push(garbage(state, format!("state of '{}', which is gone", entry.name)));
+            }
+        }
+    }
+
+    // Digests of the store and cache keys of downloads still in use
+    let mut referenced: BTreeSet<String> = BTreeSet::new();
+    for (_, lockpath) in &live {
+        if let Ok(Some(lock)) = read_lock(lockpath) {
+            wanted_downloads.extend(lock.tools.iter().map(|t| cache_key(&t.url)));
+        }
+    }
+    for dir in entries(&home.bins()) {
+        let id = file_name(&dir);
+        let Some((_, lockpath)) = live.iter().find(|(live_id, _)| *live_id == id) else {
+            found.push(garbage(dir, "project bin dir of no known dreamfile"));
+            continue;
+        };
+        let lock = read_lock(lockpath).ok().flatten();
+        if let Some(lock) = &lock {
+            referenced.extend(lock.tools.iter().map(|t| t.sha256.to_ascii_lowercase()));
+        }
+        for bin in entries(&dir) {
+            let name = file_name(&bin);
+            if lock.as_ref().is_some_and(|l| !l.installs(&name)) {
+                found.push(garbage(bin, "not in the project's lockfile anymore"));
+                continue;
+            }
+            if let Some(digest) = fs::read_link(&bin)
+                .ok()
+                .and_then(|target| target.parent().map(file_name))
+            {
+                referenced.insert(digest);
+            }
+        }
+    }
+
+    for artifact in entries(&home.store()) {
+        let name = file_name(&artifact);
+        if name.starts_with(".staging-") {
+            let age = fs::metadata(&artifact)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|m| SystemTime::now().duration_since(m).ok());
+            if age.is_some_and(|a| a > STALE_STAGING) {
+                found.push(garbage(artifact, "left over from an unfinished install"));
+            }
+        } else if !referenced.contains(&name) {
+            found.push(garbage(artifact, "stored tool no project uses"));
+        }
+    }
+
+    let downloads = downloads_dir(home);
+    for cached in entries(home.cache()) {
+        if cached != downloads {
+            found.push(garbage(cached, "cached download"));
+        }
+    }
+    for download in entries(&downloads) {
+        if !wanted_downloads.contains(&file_name(&download)) {
+            found.push(garbage(download, "cached download no project links to"));
+        }
+    }
+    Ok(Collection {
+        garbage: found,
+        forgotten,
+    })
+}
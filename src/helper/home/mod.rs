@@ -0,0 +1,131 @@
+/// Location of zzz's own data: installed tools, extensions, caches and state.
+///
+/// The root is, in order of preference:
+///  - `$ZZZ_HOME`
+///  - `~/.snooze`, when it already exists
+///  - `$XDG_DATA_HOME/snooze` (with the cache under `$XDG_CACHE_HOME/snooze`)
+///  - `~/.snooze`
+// std imports
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+const DIR_NAME: &str = "snooze";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DreamerHome {
+    root: PathBuf,
+    cache: PathBuf,
+    /// The user's home, to shorten paths for display
+    user_home: Option<PathBuf>,
+}
+
+/// A set, non-empty, absolute path from the environment.
+fn env_dir(var: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+}
+
+fn user_home() -> Option<PathBuf> {
+    env_dir("HOME").or_else(|| {
+        if cfg!(windows) {
+            env_dir("USERPROFILE")
+        } else {
+            None
+        }
+    })
+}
+
+impl DreamerHome {
+    /// Resolves the data root from the environment.
+    pub fn resolve() -> Result<DreamerHome, String> {
+        let user_home = user_home();
+        if let Some(root) = env_dir("ZZZ_HOME") {
+            return Ok(DreamerHome::at(root, user_home));
+        }
+        let Some(home) = user_home.clone() else {
+            return Err("Neither ZZZ_HOME nor HOME is set".to_string());
+        };
+        let legacy = home.join(format!(".{}", DIR_NAME));
+        if legacy.is_dir() {
+            return Ok(DreamerHome::at(legacy, user_home));
+        }
+        match env_dir("XDG_DATA_HOME") {
+            Some(data) => Ok(DreamerHome {
+                root: data.join(DIR_NAME),
+                cache: env_dir("XDG_CACHE_HOME")
+                    .map(|c| c.join(DIR_NAME))
+                    .unwrap_or_else(|| data.join(DIR_NAME).join("cache")),
+                user_home,
+            }),
+            None => Ok(DreamerHome::at(legacy, user_home)),
+        }
+    }
+
+    fn at(root: PathBuf, user_home: Option<PathBuf>) -> DreamerHome {
+        DreamerHome {
+            cache: root.join("cache"),
+            root,
+            user_home,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Installed tools, one directory per project.
+    pub fn bins(&self) -> PathBuf {
+        self.root.join("bins")
+    }
+
+    /// Where the tools of the project `id` are installed.
+    pub fn project_bins(&self, id: &str) -> PathBuf {
+        self.bins().join(id)
+    }
+
+    /// Installed tools shared by every project, one directory per download
+    /// digest.
+    pub fn store(&self) -> PathBuf {
+        self.root.join("store")
+    }
+
+    /// Executables run by 'zzz ext'.
+    pub fn ext(&self) -> PathBuf {
+        self.root.join("ext")
+    }
+
+    /// Downloads that can be thrown away and fetched again.
+    pub fn cache(&self) -> &Path {
+        &self.cache
+    }
+
+    /// Per-project load state.
+    pub fn state(&self) -> PathBuf {
+        self.root.join("state")
+    }
+
+    /// The registry of known dreamfiles.
+    pub fn registry(&self) -> PathBuf {
+        self.root.join("projects.yaml")
+    }
+
+    /// The user's settings, such as download mirrors.
+    pub fn config(&self) -> PathBuf {
+        self.root.join("config.yaml")
+    }
+
+    /// `path` with the user's home shortened to '~'.
+    pub fn display(&self, path: &Path) -> String {
+        match self
+            .user_home
+            .as_ref()
+            .and_then(|h| path.strip_prefix(h).ok())
+        {
+            Some(rest) => Path::new("~").join(rest).to_string_lossy().to_string(),
+            None => path.to_string_lossy().to_string(),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+/// Reading and writing of .zzz.lock files.
+// Extern imports
+use serde::{Deserialize, Serialize};
+
+// std imports
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const LOCK_VERSION: u32 = 1;
+
+const LOCK_HEADER: &str = "# This file is generated by zzz. Do not edit it by hand.\n";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedTool {
+    pub name: String,
+    /// The `links` key the url was picked from, for per-platform tools.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+    pub url: String,
+    pub sha256: String,
+    pub size: u64,
+    /// Executables installed for the tool, when that is not just its name.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bins: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockFile {
+    pub version: u32,
+    pub tools: Vec<LockedTool>,
+}
+
+impl LockFile {
+    pub fn new() -> LockFile {
+        LockFile {
+            version: LOCK_VERSION,
+            tools: vec![],
+        }
+    }
+
+    pub fn get(&self, name: &str, platform: Option<&str>) -> Option<&LockedTool> {
+        self.tools
+            .iter()
+            .find(|t| t.name == name && t.platform.as_deref() == platform)
+    }
+
+    /// Whether `file` in a project bin dir belongs to one of the tools.
+    pub fn installs(&self, file: &str) -> bool {
+        self.tools
+            .iter()
+            .any(|t| t.name == file || t.bins.iter().any(|b| b == file))
+    }
+}
+
+impl Default for LockFile {
+    fn default() -> Self {
+        LockFile::new()
+    }
+}
+
+/// 'foo.zzz.yaml' -> 'foo.zzz.lock', next to the dreamfile.
+pub fn lock_path(filepath: &str) -> PathBuf {
+    let stem = filepath
+        .strip_suffix(".yaml")
+        .or_else(|| filepath.strip_suffix(".yml"))
+        .unwrap_or(filepath);
+    PathBuf::from(format!("{}.lock", stem))
+}
+
+/// Reads the lockfile at `path`. A missing lockfile is not an error.
+pub fn read_lock(path: &Path) -> Result<Option<LockFile>, Box<dyn Error>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let lock: LockFile = serde_yaml::from_str(&fs::read_to_string(path)?)?;
+    if lock.version != LOCK_VERSION {
+        return Err(format!(
+            "Unsupported lockfile version {} in '{}'",
+            lock.version,
+            path.display()
+        )
+        .into());
+    }
+    Ok(Some(lock))
+}
+
+pub fn write_lock(path: &Path, lock: &LockFile) -> Result<(), Box<dyn Error>> {
+    let body = serde_yaml::to_string(lock)?;
+    fs::write(path, format!("{}{}", LOCK_HEADER, body))?;
+    Ok(())
+}
@@ -5,6 +5,7 @@ extern crate serde;
 extern crate serde_yaml;
 
 use crate::helper::colored::Colorize;
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 
 // Local imports
@@ -23,11 +24,47 @@ use crate::helper::errors::*;
 pub mod exec;
 use crate::helper::exec::*;
 
+pub mod fetch;
+
+pub mod cache;
+
+pub mod config;
+
+pub mod digest;
+
+pub mod lockfile;
+
+pub mod archive;
+
+pub mod platform;
+
+pub mod home;
+use home::DreamerHome;
+
+pub mod project;
+
+pub mod state;
+
+pub mod store;
+
+pub mod gc;
+
+pub mod yamledit;
+
+pub mod tasks;
+
+pub mod shellwords;
+
+pub mod environment;
+
+pub mod process;
+use environment::EnvMap;
+
 pub mod wizards;
 use wizards::*;
 
 // std imports
-use std::env::{self};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::path::Path;
 use std::path::PathBuf;
@@ -42,29 +79,228 @@ pub struct ProjectConfig {
     name: String,
     description: String,
     version: String,
-    isloaded: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Tool {
     name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     link: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    links: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sha512: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    bin: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    strip_components: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct DepsConfig {
+    #[serde(default, deserialize_with = "null_as_empty")]
     tools: Vec<Tool>,
 }
 
+/// Lets an emptied list ('tools:' with nothing under it) parse as empty.
+fn null_as_empty<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(Option::<Vec<T>>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Reads an 'env' map, taking numbers and booleans as their text.
+fn env_map<'de, D>(deserializer: D) -> Result<EnvMap, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let raw = Option::<IndexMap<String, serde_yaml::Value>>::deserialize(deserializer)?;
+    raw.unwrap_or_default()
+        .into_iter()
+        .map(|(name, value)| match value {
+            serde_yaml::Value::String(s) => Ok((name, s)),
+            serde_yaml::Value::Number(n) => Ok((name, n.to_string())),
+            serde_yaml::Value::Bool(b) => Ok((name, b.to_string())),
+            serde_yaml::Value::Null => Ok((name, String::new())),
+            _ => Err(serde::de::Error::custom(format!(
+                "env '{}' must be a string",
+                name
+            ))),
+        })
+        .collect()
+}
+
+/// Name of the task run when none is given.
+pub const DEFAULT_TASK: &str = "run";
+
+/// Settings a command can have on top of its line.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CommandOptions {
+    /// Overrides the task's 'continue_on_error' for this command
+    continue_on_error: Option<bool>,
+    /// Overrides the task's 'cwd' for this command
+    cwd: Option<String>,
+    /// Seconds the command may run for
+    timeout: Option<u64>,
+}
+
+/// A command is a plain line split into words, or a line for the shell:
+/// 'sh: <line>' or '{ cmd: <line>, shell: true }'.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum CommandRepr {
+    Line(String),
+    Shell {
+        sh: String,
+        #[serde(flatten)]
+        options: CommandOptions,
+    },
+    Full {
+        cmd: String,
+        #[serde(default)]
+        shell: bool,
+        #[serde(flatten)]
+        options: CommandOptions,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "CommandRepr")]
+pub struct TaskCommand {
+    cmd: String,
+    shell: bool,
+    options: CommandOptions,
+}
+
+impl From<CommandRepr> for TaskCommand {
+    fn from(repr: CommandRepr) -> Self {
+        match repr {
+            CommandRepr::Line(line) => match line.strip_prefix("sh:") {
+                Some(cmd) => TaskCommand {
+                    cmd: cmd.trim_start().to_string(),
+                    shell: true,
+                    options: CommandOptions::default(),
+                },
+                None => TaskCommand {
+                    cmd: line,
+                    shell: false,
+                    options: CommandOptions::default(),
+                },
+            },
+            CommandRepr::Shell { sh, options } => TaskCommand {
+                cmd: sh,
+                shell: true,
+                options,
+            },
+            CommandRepr::Full {
+                cmd,
+                shell,
+                options,
+            } => TaskCommand {
+                cmd,
+                shell,
+                options,
+            },
+        }
+    }
+}
+
+/// A task is either a bare list of commands, or a mapping with a 'run' list.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TaskRepr {
+    Commands(Vec<TaskCommand>),
+    Full {
+        #[serde(default)]
+        run: Vec<TaskCommand>,
+        #[serde(default)]
+        depends_on: Vec<String>,
+        #[serde(default)]
+        continue_on_error: bool,
+        #[serde(default, deserialize_with = "env_map")]
+        env: EnvMap,
+        cwd: Option<String>,
+        timeout: Option<u64>,
+        #[serde(default)]
+        parallel: bool,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "TaskRepr")]
+pub struct Task {
+    run: Vec<TaskCommand>,
+    depends_on: Vec<String>,
+    /// Keep going with the next command when one of this task's fails
+    continue_on_error: bool,
+    /// Variables set for this task's commands, over the project's
+    env: EnvMap,
+    /// Directory the commands run in, relative to the dreamfile's
+    cwd: Option<String>,
+    /// Seconds the whole task may run for
+    timeout: Option<u64>,
+    /// Run the commands of this task at the same time
+    parallel: bool,
+}
+
+impl From<TaskRepr> for Task {
+    fn from(repr: TaskRepr) -> Self {
+        match repr {
+            TaskRepr::Commands(run) => Task {
+                run,
+                depends_on: vec![],
+                continue_on_error: false,
+                env: EnvMap::new(),
+                cwd: None,
+                timeout: None,
+                parallel: false,
+            },
+            TaskRepr::Full {
+                run,
+                depends_on,
+                continue_on_error,
+                env,
+                cwd,
+                timeout,
+                parallel,
+            } => Task {
+                run,
+                depends_on,
+                continue_on_error,
+                env,
+                cwd,
+                timeout,
+                parallel,
+            },
+        }
+    }
+}
+
+/// The 'do' section: named tasks, in the order they are declared.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct RunConfig {
-    run: Vec<String>,
+    tasks: IndexMap<String, Task>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ZzzConfig {
     project: ProjectConfig,
+    /// Variables set for every command, over the '.env' file
+    #[serde(default, deserialize_with = "env_map")]
+    env: EnvMap,
     r#do: RunConfig,
+    #[serde(default)]
     deps: DepsConfig,
 }
 
@@ -77,19 +313,57 @@ fn usagenb(cmd: &str) {
 }
 */
 
-pub fn run(argsv: Vec<String>, global_opts: &[bool]) -> Result<(), Box<dyn Error>> {
-    if check_arg_len(argsv.clone(), 2) {
-        usage_and_quit(RUNCMD.name, "Missing Filename!")
-    }
-
-    let _ = match read_file(&argsv, 2, RUNCMD) {
-        Ok(v_file) => run_exec(v_file.0, v_file.1, global_opts.to_vec()),
+/// Runs a task, returning the exit status the zzz process should end with.
+pub fn run(
+    argsv: Vec<String>,
+    home_dir: Result<DreamerHome, String>,
+    global_opts: &[bool],
+) -> Result<i32, Box<dyn Error>> {
+    let (argsv, jobs) = match take_jobs(argsv) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            usage_and_quit(RUNCMD.name, &e);
+            return Err(e.into());
+        }
+    };
+    let mut file_args: Vec<String> = argsv.into_iter().filter(|a| !a.starts_with('-')).collect();
+    // 'zzz run <task>' with a discovered dreamfile, or 'zzz run <file> [task]'
+    let task = match file_args.len() {
+        3 if !dreamfile_exists(&file_args[2]) => file_args.pop(),
+        4.. => Some(file_args.remove(3)),
+        _ => None,
+    };
+    match read_file(&file_args, 2, RUNCMD) {
+        Ok(v_file) => run_exec(v_file.0, v_file.1, task, jobs, home_dir, global_opts.to_vec()),
         Err(file) => {
             MISSINGFILEERROR.show_error(&file.1, global_opts);
             Err("Missing File".into())
         }
-    };
-    Ok(())
+    }
+}
+
+/// Pulls '-j N' ('-jN', '--jobs N', '--jobs=N') out of `argsv`.
+fn take_jobs(argsv: Vec<String>) -> Result<(Vec<String>, Option<usize>), String> {
+    let mut rest = vec![];
+    let mut jobs = None;
+    let mut args = argsv.into_iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "-j" | "--jobs" => args.next(),
+            _ => match arg.strip_prefix("--jobs=").or(arg.strip_prefix("-j")) {
+                Some(value) => Some(value.to_string()),
+                None => {
+                    rest.push(arg);
+                    continue;
+                }
+            },
+        };
+        match value.as_deref().map(str::parse::<usize>) {
+            Some(Ok(n)) if n > 0 => jobs = Some(n),
+            _ => return Err("'-j' needs a number of jobs above 0".to_string()),
+        }
+    }
+    Ok((rest, jobs))
 }
 
 pub fn help(argsv: Vec<String>) {
@@ -140,7 +414,7 @@ pub fn new(
 pub fn load(
     argsv: Vec<String>,
     env_cmds: Vec<String>,
-    home_dir: Result<String, env::VarError>,
+    home_dir: Result<DreamerHome, String>,
     global_opts: &[bool],
 ) -> Result<(), Box<dyn Error>> {
     match load_deps(
@@ -154,7 +428,7 @@ pub fn load(
             Err("Error Loading".into())
         }
         Ok(result) => {
-            init_shell(result.0.clone(), home_dir.clone(), result.1);
+            init_shell(result.0.clone(), home_dir.clone(), result.1, result.2);
             Ok(())
         }
     }
@@ -163,7 +437,7 @@ pub fn load(
 pub fn load_and_run(
     argsv: Vec<String>,
     env_cmds: Vec<String>,
-    home_dir: Result<String, env::VarError>,
+    home_dir: Result<DreamerHome, String>,
     global_opts: &[bool],
 ) -> Result<(), Box<dyn Error>> {
     match load_deps(
@@ -176,13 +450,17 @@ pub fn load_and_run(
             quit(2);
             Err("Error Loading".into())
         }
-        Ok(result) => match run(argsv, global_opts) {
+        Ok(result) => match run(argsv, home_dir.clone(), global_opts) {
             Err(_) => {
                 quit(2);
                 Err("Error Running".into())
             }
+            Ok(code) if code != 0 => {
+                quit(code);
+                Err("Error Running".into())
+            }
             Ok(..) => {
-                init_shell(result.0, home_dir, result.1);
+                init_shell(result.0, home_dir, result.1, result.2);
                 Ok(())
             }
         },
@@ -190,10 +468,6 @@ pub fn load_and_run(
 }
 
 pub fn list(argsv: Vec<String>, way: usize, global_opts: &[bool]) -> Result<(), Box<dyn Error>> {
-    if check_arg_len(argsv.clone(), 2) {
-        usage_and_quit(LISTCMD.name, "Missing Filename!")
-    }
-
     let _ = match read_file(&argsv, 2, LISTCMD) {
         Ok(v_file) => {
             let result = list_exec(v_file.0, v_file.1, way, global_opts);
@@ -220,10 +494,14 @@ pub fn add(argsv: Vec<String>, global_opts: &[bool]) -> Result<(), Box<dyn Error
     } else {
         let dep_to_get = &argsv[2];
         match argsv.len() {
-            4 => {
-                let _ = match read_file_gpath(&argsv[3]) {
-                    Ok(v_file) => {
-                        let result = add_exec(&v_file.1, dep_to_get, global_opts);
+            3 | 4 => {
+                let filename = match argsv.get(3) {
+                    Some(filename) => filename.clone(),
+                    None => discover_dreamfile(ADDCMD),
+                };
+                let _ = match read_file_gpath(&filename) {
+                    Ok(..) => {
+                        let result = add_exec(&filename, dep_to_get, global_opts);
                         Ok(result)
                     }
                     Err(file) => {
@@ -245,20 +523,22 @@ pub fn add(argsv: Vec<String>, global_opts: &[bool]) -> Result<(), Box<dyn Error
     }
 }
 
-pub fn extension(args: Vec<String>, home_dir: Result<String, env::VarError>, global_opts: &[bool]) {
+pub fn extension(args: Vec<String>, home_dir: Result<DreamerHome, String>, global_opts: &[bool]) {
     if check_arg_len(args.clone(), 2) {
         usage_and_quit(EXTCMD.name, "No Extension!")
     }
     extension_exec(args, home_dir, global_opts)
 }
 
-pub fn remove(args: Vec<String>, global_opts: &[bool]) {
-    if check_arg_len(args.clone(), 3) {
-        let _ = remove_exec(&args[3], &args[2], global_opts);
+pub fn remove(args: Vec<String>, home_dir: Result<DreamerHome, String>, global_opts: &[bool]) {
+    if args.len() >= 4 {
+        let _ = remove_exec(&args[3], &args[2], &home_dir, global_opts);
+    } else if args.len() == 3 {
+        let _ = remove_exec(&discover_dreamfile(REMOVECMD), &args[2], &home_dir, global_opts);
     } else {
         match remove_cmd_wizard() {
             Ok(res) => {
-                let _ = remove_exec(&res.0, &res.1, global_opts);
+                let _ = remove_exec(&res.0, &res.1, &home_dir, global_opts);
             }
             Err(..) => {
                 quit(4);
@@ -267,6 +547,53 @@ pub fn remove(args: Vec<String>, global_opts: &[bool]) {
     }
 }
 
+pub fn lock(
+    args: Vec<String>,
+    home_dir: Result<DreamerHome, String>,
+    global_opts: &[bool],
+) -> Result<(), Box<dyn Error>> {
+    let update = args.contains(&"--update".to_string());
+    let argsv: Vec<String> = args.into_iter().filter(|a| !a.starts_with('-')).collect();
+    match read_file(&argsv, 2, LOCKCMD) {
+        Ok(v_file) => lock_exec(v_file.0, v_file.1, home_dir, update, global_opts),
+        Err(file) => {
+            MISSINGFILEERROR.show_error(&file.1, global_opts);
+            Err("Missing File".into())
+        }
+    }
+}
+
+pub fn gc(
+    args: Vec<String>,
+    home_dir: Result<DreamerHome, String>,
+    global_opts: &[bool],
+) -> Result<(), Box<dyn Error>> {
+    let dry_run = args.contains(&"--dry-run".to_string());
+    match home_dir {
+        Ok(home) => gc_exec(&home, dry_run, global_opts),
+        Err(e) => {
+            errprint!("{}", e);
+            Err(e.into())
+        }
+    }
+}
+
+pub fn fetch(
+    args: Vec<String>,
+    home_dir: Result<DreamerHome, String>,
+    global_opts: &[bool],
+) -> Result<(), Box<dyn Error>> {
+    let all_platforms = args.contains(&"--all-platforms".to_string());
+    let argsv: Vec<String> = args.into_iter().filter(|a| !a.starts_with('-')).collect();
+    match read_file(&argsv, 2, FETCHCMD) {
+        Ok(v_file) => fetch_exec(v_file.0, v_file.1, home_dir, all_platforms, global_opts),
+        Err(file) => {
+            MISSINGFILEERROR.show_error(&file.1, global_opts);
+            Err("Missing File".into())
+        }
+    }
+}
+
 pub fn invalid_args_notify(args: Vec<String>) {
     errprint!(
         "{0}{1}{2}",
@@ -334,9 +661,8 @@ fn argshelp_exec(s: Vec<char>, t: Vec<char>, way: usize) -> Result<String, Strin
             for i in 0..m {
                 let mut j = 0;
                 //println!("{}", i + j);
-                while j < n && s[i + j] == t[j] {
+                if j < n && s[i + j] == t[j] {
                     j += 1;
-                    break;
                 }
                 if j == n {
                     if n == m {
@@ -370,18 +696,18 @@ fn argshelp_exec(s: Vec<char>, t: Vec<char>, way: usize) -> Result<String, Strin
             }
         }
     }
-    return Err("notfound".into());
+    Err("notfound".into())
 }
 
-pub fn argshelp(args: &Vec<String>, cmdtc: &Cmd) -> Result<String, String> {
+pub fn argshelp(args: &[String], cmdtc: &Cmd) -> Result<String, String> {
     let t: Vec<char> = cmdtc.name.chars().collect();
     let s: Vec<char> = args[1].chars().collect();
     let (m, n) = (s.len(), t.len());
     if m < n {
         //println!("a");
-        return argshelp_exec(t, s, 1); // swap(t, s)
+        argshelp_exec(t, s, 1) // swap(t, s)
     } else {
         //println!("b");
-        return argshelp_exec(s, t, 0);
+        argshelp_exec(s, t, 0)
     }
 }
@@ -0,0 +1,129 @@
+/// Platform detection and link templating for tools.
+// std imports
+use std::{error::Error, fmt};
+
+use super::Tool;
+
+#[derive(Debug)]
+pub struct NoPlatformBuild {
+    pub tool: String,
+    pub platform: String,
+    pub available: Vec<String>,
+}
+
+impl fmt::Display for NoPlatformBuild {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "No build of '{0}' for this platform ({1})",
+            self.tool, self.platform
+        )?;
+        if !self.available.is_empty() {
+            write!(f, ", available: {}", self.available.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for NoPlatformBuild {}
+
+/// A link picked for the current platform. `key` is the entry of
+/// `Tool.links` it came from, or None for the plain `Tool.link`.
+pub struct ResolvedLink {
+    pub url: String,
+    pub key: Option<String>,
+}
+
+/// The os as written in links: 'linux', 'darwin', 'windows'...
+pub fn os_name() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "darwin",
+        os => os,
+    }
+}
+
+pub fn arch_name() -> &'static str {
+    std::env::consts::ARCH
+}
+
+pub fn platform_name() -> String {
+    format!("{0}-{1}", os_name(), arch_name())
+}
+
+fn os_aliases() -> Vec<&'static str> {
+    match std::env::consts::OS {
+        "macos" => vec!["darwin", "macos"],
+        os => vec![os],
+    }
+}
+
+fn arch_aliases() -> Vec<&'static str> {
+    match std::env::consts::ARCH {
+        "x86_64" => vec!["x86_64", "amd64"],
+        "aarch64" => vec!["aarch64", "arm64"],
+        "x86" => vec!["x86", "i686", "386"],
+        arch => vec![arch],
+    }
+}
+
+/// Keys of `Tool.links` matching this machine, most specific first.
+fn candidate_keys() -> Vec<String> {
+    let mut keys = vec![];
+    for os in os_aliases() {
+        for arch in arch_aliases() {
+            keys.push(format!("{0}-{1}", os, arch));
+        }
+    }
+    for os in os_aliases() {
+        keys.push(os.to_string());
+    }
+    keys
+}
+
+/// Substitutes the {os}, {arch} and {version} placeholders of a link.
+pub fn expand_link(tool: &Tool, link: &str) -> Result<String, Box<dyn Error>> {
+    let link = link.replace("{os}", os_name()).replace("{arch}", arch_name());
+    if !link.contains("{version}") {
+        return Ok(link);
+    }
+    match &tool.version {
+        Some(version) => Ok(link.replace("{version}", version)),
+        None => Err(format!("'{}' uses {{version}} in its link but has no version", tool.name).into()),
+    }
+}
+
+/// Every link of `tool`, for any platform, that can be expanded.
+pub fn all_links(tool: &Tool) -> Vec<String> {
+    let mut urls: Vec<String> = vec![];
+    let links = tool.links.values().chain(Some(&tool.link).filter(|l| !l.is_empty()));
+    for url in links.filter_map(|l| expand_link(tool, l).ok()) {
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+/// Picks the link of `tool` for this platform.
+pub fn resolve_link(tool: &Tool) -> Result<ResolvedLink, Box<dyn Error>> {
+    for key in candidate_keys() {
+        if let Some(link) = tool.links.get(&key) {
+            return Ok(ResolvedLink {
+                url: expand_link(tool, link)?,
+                key: Some(key),
+            });
+        }
+    }
+    if !tool.link.is_empty() {
+        return Ok(ResolvedLink {
+            url: expand_link(tool, &tool.link)?,
+            key: None,
+        });
+    }
+    Err(NoPlatformBuild {
+        tool: tool.name.clone(),
+        platform: platform_name(),
+        available: tool.links.keys().cloned().collect(),
+    }
+    .into())
+}
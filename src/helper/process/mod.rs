@@ -0,0 +1,237 @@
+/// Waiting on task commands with a deadline, killing their whole process
+/// tree once it passes, and prefixing their output when they run alongside
+/// others.
+///
+/// On unix a command with a deadline runs in a process group of its own.
+/// zzz forwards SIGINT and SIGTERM to those groups, and hands the terminal
+/// over to the group while it runs in the foreground, so that Ctrl-C and
+/// reading the terminal work as if it had been started by the shell.
+// std imports
+use std::{
+    io::{self, BufRead, BufReader, Read},
+    process::{Child, Command, ExitStatus, Stdio},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// How long to sleep between checks on a child with a deadline.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+pub enum Waited {
+    Exited(ExitStatus),
+    TimedOut,
+}
+
+/// Runs `command` to completion, or until `deadline`. With a `prefix`, the
+/// command gets no stdin and every line it prints is shown after `prefix`.
+pub fn run_until(
+    command: &mut Command,
+    deadline: Option<Instant>,
+    prefix: Option<&str>,
+) -> io::Result<Waited> {
+    if deadline.is_none() && prefix.is_none() {
+        return command.status().map(Waited::Exited);
+    }
+    // In a process group of its own, so that everything it starts can be
+    // killed along with it
+    #[cfg(unix)]
+    let foreground = deadline.is_some() && prefix.is_none() && group::owns_terminal();
+    #[cfg(unix)]
+    if deadline.is_some() {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+        if foreground {
+            // Only calls async-signal-safe functions
+            unsafe {
+                command.pre_exec(group::take_terminal);
+            }
+        }
+    }
+    if prefix.is_some() {
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+    }
+    let mut child = command.spawn()?;
+    #[cfg(unix)]
+    let _group = deadline.map(|_| group::Registered::new(child.id(), foreground));
+    let forwarders = match prefix {
+        Some(prefix) => forward_output(&mut child, prefix),
+        None => vec![],
+    };
+    let waited = match deadline {
+        Some(deadline) => wait_until(&mut child, deadline),
+        None => child.wait().map(Waited::Exited),
+    };
+    for forwarder in forwarders {
+        let _ = forwarder.join();
+    }
+    waited
+}
+
+/// Prints the lines of `pipe` after `prefix`, on stdout or stderr.
+fn forward<R: Read + Send + 'static>(pipe: R, prefix: String, to_stderr: bool) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        let mut line = vec![];
+        while reader.read_until(b'\n', &mut line).is_ok_and(|n| n > 0) {
+            let text = String::from_utf8_lossy(&line);
+            let text = text.trim_end_matches(['\n', '\r']);
+            if to_stderr {
+                eprintln!("{0} {1}", prefix, text);
+            } else {
+                println!("{0} {1}", prefix, text);
+            }
+            line.clear();
+        }
+    })
+}
+
+fn forward_output(child: &mut Child, prefix: &str) -> Vec<JoinHandle<()>> {
+    let mut forwarders = vec![];
+    if let Some(out) = child.stdout.take() {
+        forwarders.push(forward(out, prefix.to_string(), false));
+    }
+    if let Some(err) = child.stderr.take() {
+        forwarders.push(forward(err, prefix.to_string(), true));
+    }
+    forwarders
+}
+
+fn wait_until(child: &mut Child, deadline: Instant) -> io::Result<Waited> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Waited::Exited(status));
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            kill_tree(child);
+            return Ok(Waited::TimedOut);
+        }
+        thread::sleep(POLL_INTERVAL.min(deadline - now));
+    }
+}
+
+#[cfg(unix)]
+mod group {
+    use std::sync::{
+        atomic::{AtomicI32, Ordering},
+        Once,
+    };
+
+    /// How many groups signals are forwarded to; commands in more groups
+    /// than this at once only get killed on timeout.
+    const MAX_GROUPS: usize = 64;
+
+    /// Process groups of the commands running now, 0 for a free slot.
+    static GROUPS: [AtomicI32; MAX_GROUPS] = [const { AtomicI32::new(0) }; MAX_GROUPS];
+    static HANDLERS: Once = Once::new();
+
+    extern "C" fn forward(sig: libc::c_int) {
+        for group in &GROUPS {
+            let pgid = group.load(Ordering::SeqCst);
+            if pgid > 0 {
+                unsafe {
+                    libc::kill(-pgid, sig);
+                }
+            }
+        }
+        // Then go down the way zzz would have without the handler
+        unsafe {
+            libc::signal(sig, libc::SIG_DFL);
+            libc::raise(sig);
+        }
+    }
+
+    fn install_handlers() {
+        HANDLERS.call_once(|| unsafe {
+            let handler = forward as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            for sig in [libc::SIGINT, libc::SIGTERM] {
+                // A signal zzz was started ignoring stays ignored
+                if libc::signal(sig, handler) == libc::SIG_IGN {
+                    libc::signal(sig, libc::SIG_IGN);
+                }
+            }
+        });
+    }
+
+    /// Whether zzz runs in the foreground of a terminal on stdin.
+    pub fn owns_terminal() -> bool {
+        unsafe { libc::isatty(0) == 1 && libc::tcgetpgrp(0) == libc::getpgrp() }
+    }
+
+    /// Makes the process group of the calling process the foreground one of
+    /// the terminal. Without blocking SIGTTOU, a background group asking
+    /// for the terminal is stopped.
+    fn set_foreground(pgid: libc::pid_t) {
+        unsafe {
+            let mut ttou: libc::sigset_t = std::mem::zeroed();
+            let mut old: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut ttou);
+            libc::sigaddset(&mut ttou, libc::SIGTTOU);
+            libc::pthread_sigmask(libc::SIG_BLOCK, &ttou, &mut old);
+            libc::tcsetpgrp(0, pgid);
+            libc::pthread_sigmask(libc::SIG_SETMASK, &old, std::ptr::null_mut());
+        }
+    }
+
+    /// Run in the child before exec, so that it never reads the terminal
+    /// from the background.
+    pub fn take_terminal() -> std::io::Result<()> {
+        unsafe {
+            libc::setpgid(0, 0);
+            set_foreground(libc::getpid());
+        }
+        Ok(())
+    }
+
+    /// A running command's group, which signals are forwarded to until it
+    /// is dropped. Gives the terminal back to zzz if the group had it.
+    pub struct Registered {
+        slot: Option<usize>,
+        foreground: bool,
+    }
+
+    impl Registered {
+        pub fn new(pid: u32, foreground: bool) -> Registered {
+            install_handlers();
+            let slot = GROUPS.iter().position(|g| {
+                g.compare_exchange(0, pid as i32, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+            });
+            Registered { slot, foreground }
+        }
+    }
+
+    impl Drop for Registered {
+        fn drop(&mut self) {
+            if let Some(slot) = self.slot {
+                GROUPS[slot].store(0, Ordering::SeqCst);
+            }
+            if self.foreground {
+                set_foreground(unsafe { libc::getpgrp() });
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn kill_tree(child: &mut Child) {
+    // The group id is the pid of its leader
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    let _ = child.wait();
+}
+
+#[cfg(windows)]
+fn kill_tree(child: &mut Child) {
+    let killed = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &child.id().to_string()])
+        .status();
+    if !killed.is_ok_and(|s| s.success()) {
+        let _ = child.kill();
+    }
+    let _ = child.wait();
+}
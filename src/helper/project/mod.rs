@@ -0,0 +1,90 @@
+/// Stable project identities, persisted in projects.yaml under the zzz home.
+// Extern imports
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// std imports
+use std::{collections::BTreeMap, error::Error, fs};
+
+use super::home::DreamerHome;
+use super::resource::calculate_hash;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectEntry {
+    pub id: String,
+    pub name: String,
+}
+
+/// Known dreamfiles, keyed by their canonical path.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Registry {
+    #[serde(default)]
+    pub projects: BTreeMap<String, ProjectEntry>,
+}
+
+pub fn read_registry(home: &DreamerHome) -> Result<Registry, Box<dyn Error>> {
+    let path = home.registry();
+    if !path.exists() {
+        return Ok(Registry::default());
+    }
+    Ok(serde_yaml::from_str(&fs::read_to_string(path)?)?)
+}
+
+pub fn write_registry(home: &DreamerHome, registry: &Registry) -> Result<(), Box<dyn Error>> {
+    let path = home.registry();
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, serde_yaml::to_string(registry)?)?;
+    Ok(())
+}
+
+fn stable_id(canonical: &str, name: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(canonical.as_bytes());
+    hasher.update([0]);
+    hasher.update(name.as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Moves a bin dir named after the old, name-only hash over to `id`, so
+/// tools installed by earlier versions of zzz aren't downloaded again.
+fn migrate_legacy_bins(home: &DreamerHome, name: &str, id: &str) {
+    let legacy = home.project_bins(&calculate_hash(&name).to_string());
+    let current = home.project_bins(id);
+    if legacy.is_dir() && !current.exists() {
+        let _ = fs::rename(legacy, current);
+    }
+}
+
+/// The id of the project whose dreamfile is at `filepath`, if it has one.
+pub fn known_id(home: &DreamerHome, filepath: &str) -> Option<String> {
+    let canonical = fs::canonicalize(filepath).ok()?.to_string_lossy().to_string();
+    let registry = read_registry(home).ok()?;
+    registry.projects.get(&canonical).map(|e| e.id.clone())
+}
+
+/// Returns the id of the project whose dreamfile is at `filepath`. The id
+/// is derived from the canonical path and project name the first time the
+/// project is seen, then kept as-is in the registry.
+pub fn project_id(home: &DreamerHome, filepath: &str, name: &str) -> Result<String, Box<dyn Error>> {
+    let canonical = fs::canonicalize(filepath)?.to_string_lossy().to_string();
+    let mut registry = read_registry(home)?;
+    if let Some(entry) = registry.projects.get(&canonical) {
+        return Ok(entry.id.clone());
+    }
+    let id = stable_id(&canonical, name);
+    migrate_legacy_bins(home, name, &id);
+    registry.projects.insert(
+        canonical,
+        ProjectEntry {
+            id: id.clone(),
+            name: name.to_string(),
+        },
+    );
+    write_registry(home, &registry)?;
+    Ok(id)
+}
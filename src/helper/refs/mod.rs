@@ -1,10 +1,4 @@
-/// Defnew
-//ion of the Cmd type, and constant values for the commands.
-
-pub trait EntryFunc {
-    fn go() -> Result<(), ()>;
-}
-
+/// Definition of the Cmd type, and constant values for the commands.
 pub struct Cmd<'a> {
     pub name: &'a str,
     pub desc: &'a str,
@@ -16,8 +10,8 @@ pub struct Cmd<'a> {
 pub const RUNCMD: Cmd = Cmd {
     name: "run",
     desc: "Executes a .zzz.yaml file",
-    longdesc: "Runs the content in the .zzz.yaml file provide by <filename>.",
-    usage: "run <filename>",
+    longdesc: "Runs a task of the .zzz.yaml file provide by [filename]. Tasks are the named lists in the 'do' section, and 'run' is used when no task is given. If no filename is provided, zzz looks for a dreamfile in the current directory and its parents.! Pass '-j N' to run independent tasks at the same time, at most N commands at once. The commands of a 'parallel' task run at most one per CPU at once, or N with '-j N'.",
+    usage: "run [filename] [task] [-j N]",
     aliases: ["run", "r"],
 };
 
@@ -40,15 +34,15 @@ pub const NEWCMD: Cmd = Cmd {
 pub const LOADCMD: Cmd = Cmd {
     name: "load",
     desc: "Load a .zzz.yaml file",
-    longdesc: "Loads and grabs the dependancies found in a dreamfile. If no filename is provided, zzz will prompt for one.",
-    usage: "load [filename]",
+    longdesc: "Loads and grabs the dependancies found in a dreamfile. If no filename is provided, zzz looks for one in the current directory and its parents, and prompts if there are several.! Pass '--offline' (or set ZZZ_OFFLINE=1) to install only from the download cache that 'zzz fetch' fills.",
+    usage: "load [filename] [--offline]",
     aliases: ["load", "l"],
 };
 
 pub const LISTCMD: Cmd = Cmd {
     name: "list",
-    desc: "Lists all dependancies in a dreamfile",
-    longdesc: "Lists all dependancies in a dreamfile. If no filename is provided, zzz will prompt for one.",
+    desc: "Lists all dependancies and tasks in a dreamfile",
+    longdesc: "Lists all dependancies and tasks in a dreamfile. If no filename is provided, zzz looks for one in the current directory and its parents, and prompts if there are several.",
     usage: "list [filename]",
     aliases: ["list", "L"],
 };
@@ -56,15 +50,15 @@ pub const LISTCMD: Cmd = Cmd {
 pub const ADDCMD: Cmd = Cmd {
     name: "add",
     desc: "Adds a dependancy to a dreamfile",
-    longdesc: "Adds a dependancy to a dreamfile. If arguments are missing, a wizard will launch to choose one.",
-    usage: "add <dependancy> <filename>",
+    longdesc: "Adds a dependancy to a dreamfile. Use 'name@1.2.3' to pin a version, which is substituted for {version} in the link. Adding a tool that is already there updates its version. If arguments are missing, a wizard will launch to choose one.",
+    usage: "add <dependancy>[@version] [filename]",
     aliases: ["add", "a"],
 };
 
 pub const EXTCMD: Cmd = Cmd {
     name: "ext",
     desc: "Runs an extension",
-    longdesc: "Runs an extension. Extensions are found in '$HOME/.zzz/ext'. !If arguments are missing, a wizard will launch to choose one.",
+    longdesc: "Runs an extension. Extensions are found in the 'ext' directory of the zzz home ($ZZZ_HOME, or '~/.snooze' by default), then on PATH. !If arguments are missing, a wizard will launch to choose one.",
     usage: "ext <extension> [arguments]",
     aliases: ["ext", "@"],
 };
@@ -72,11 +66,36 @@ pub const EXTCMD: Cmd = Cmd {
 pub const REMOVECMD: Cmd = Cmd {
     name: "remove",
     desc: "Removes a dependancy from a .zzz.yaml file",
-    longdesc: "Removes a dependancy from a .zzz.yaml file provide by <filename>.",
-    usage: "remove <filename>",
+    longdesc: "Removes a dependancy from a .zzz.yaml file provide by <filename>, along with its lockfile entry and installed executables.",
+    usage: "remove <dependancy> [filename]",
     aliases: ["remove", "rm"],
 };
 
-pub const AVAILABLE_CMDS: [&Cmd; 8] = [
-    &HELPCMD, &LOADCMD, &RUNCMD, &NEWCMD, &LISTCMD, &ADDCMD, &EXTCMD, &REMOVECMD,
+pub const LOCKCMD: Cmd = Cmd {
+    name: "lock",
+    desc: "Writes the .zzz.lock file for a dreamfile",
+    longdesc: "Resolves the tools of a dreamfile and records their links and digests in a .zzz.lock file next to it. Tools already in the lockfile are kept as they are.! Pass '--update' to re-resolve every tool.",
+    usage: "lock [filename] [--update]",
+    aliases: ["lock", "lk"],
+};
+
+pub const GCCMD: Cmd = Cmd {
+    name: "gc",
+    desc: "Removes tools and downloads no project uses anymore",
+    longdesc: "Forgets dreamfiles that are gone and removes what no known dreamfile uses: their bin directories, tools dropped from a lockfile, unused entries of the tool store and cached downloads.! Pass '--dry-run' to only see what would be removed and how much space it takes.",
+    usage: "gc [--dry-run]",
+    aliases: ["gc", "clean"],
+};
+
+pub const FETCHCMD: Cmd = Cmd {
+    name: "fetch",
+    desc: "Downloads the tools of a dreamfile into the download cache",
+    longdesc: "Downloads the tools of a dreamfile into the download cache without installing them, so that 'zzz load --offline' can install them later without the network. Downloads are checked against the digests of the dreamfile and its lockfile.! Pass '--all-platforms' to also fetch the builds for other platforms.",
+    usage: "fetch [filename] [--all-platforms]",
+    aliases: ["fetch", "fe"],
+};
+
+pub const AVAILABLE_CMDS: [&Cmd; 11] = [
+    &HELPCMD, &LOADCMD, &RUNCMD, &NEWCMD, &LISTCMD, &ADDCMD, &EXTCMD, &REMOVECMD, &LOCKCMD,
+    &GCCMD, &FETCHCMD,
 ];
@@ -5,7 +5,7 @@ extern crate colored;
 use crate::helper::colored::Colorize;
 
 // Local Imports
-use super::refs::{ADDCMD, EXTCMD, HELPCMD, LISTCMD, LOADCMD, NEWCMD, RUNCMD};
+use super::refs::{ADDCMD, EXTCMD, FETCHCMD, GCCMD, HELPCMD, LISTCMD, LOADCMD, LOCKCMD, NEWCMD, RUNCMD};
 use crate::helper::{usage, Cmd, PathBuf, NOFILESERROR, errors::Printerror};
 
 // std imports
@@ -19,6 +19,7 @@ use std::{
     io,
     io::{BufRead, Write},
     iter::*,
+    path::Path,
 };
 
 macro_rules! errprint {
@@ -134,6 +135,8 @@ pub fn printusage(msg: &str) {
     }
 }
 
+/// Name-only project hash used by older versions for bin dirs. It is not
+/// stable across Rust releases, so it is only kept to migrate those dirs.
 pub fn calculate_hash<T: Hash>(t: &T) -> u64 {
     let mut s = DefaultHasher::new();
     t.hash(&mut s);
@@ -185,20 +188,26 @@ pub fn option_list(kind: &str, opts: Vec<String>, msg: &str) -> Vec<char> {
         println!("\t  {0}: {1}", i + 1, el);
         //count += 1;
     }
-    let result: String = questionprint!("==> ");
-    let result_c: Vec<char> = result.chars().collect();
-    //println!("{}", result_c.len());
-    if result_c.len() == 1 {
-        match result_c[0] {
-            '1'..='9' => return result_c,
-            _ => {
-                quit(0);
+    loop {
+        let result: String = questionprint!("==> ");
+        let result_c: Vec<char> = result.chars().collect();
+        //println!("{}", result_c.len());
+        if result_c.len() == 1 {
+            match result_c[0] {
+                c @ '1'..='9' if c.to_digit(10).unwrap() as usize <= opts.len() => return result_c,
+                c @ '1'..='9' => {
+                    errprint!("There is no option {}", c);
+                    continue;
+                }
+                _ => {
+                    quit(0);
+                }
             }
+        } else {
+            quit(0);
         }
-    } else {
-        quit(0);
+        return result_c;
     }
-    result_c
 }
 
 pub fn quit(status: i32) {
@@ -279,7 +288,7 @@ pub fn check_arg_len(argsv: Vec<String>, lentocheck: usize) -> bool {
     argsv.len() == lentocheck
 }
 
-pub fn matchcmd(cmd: &str) -> Result<Cmd, String> {
+pub fn matchcmd(cmd: &str) -> Result<Cmd<'_>, String> {
     match cmd {
         "help" => Ok(HELPCMD),
         "run" => Ok(RUNCMD),
@@ -288,35 +297,66 @@ pub fn matchcmd(cmd: &str) -> Result<Cmd, String> {
         "list" => Ok(LISTCMD),
         "add" => Ok(ADDCMD),
         "ext" => Ok(EXTCMD),
+        "lock" => Ok(LOCKCMD),
+        "gc" => Ok(GCCMD),
+        "fetch" => Ok(FETCHCMD),
         &_ => Err("INVALID CMD".to_string()),
     }
 }
 
 pub fn read_file(
-    argsv: &Vec<String>,
+    argsv: &[String],
     to_open: usize,
     caller: Cmd,
 ) -> Result<(File, String), (String, String)> {
-    if to_open < argsv.len() {
-        let filepath = argsv[to_open].to_string().to_owned() + ".zzz.yml";
-        let file: Result<File, std::io::Error> = File::open(filepath.clone());
-        match file {
-            Ok(v_file) => Ok((v_file, filepath)),
-            Err(_error) => {
-                let filepath = argsv[to_open].to_string().to_owned() + ".zzz.yaml";
-                let file: Result<File, std::io::Error> = File::open(filepath.clone());
-                match file {
-                    Ok(v_file) => Ok((v_file, filepath)),
-                    Err(error) => Err((error.to_string(), filepath)),
-                }
-            }
+    // Flags may appear anywhere, they don't count as arguments
+    let positional: Vec<&String> = argsv.iter().filter(|a| !a.starts_with('-')).collect();
+    let base = match positional.get(to_open) {
+        Some(name) => name.to_string(),
+        None => discover_dreamfile(caller),
+    };
+    if base.ends_with(".zzz.yml") || base.ends_with(".zzz.yaml") {
+        return read_file_gpath_no_f(&base);
+    }
+    read_file_gpath(&base)
+}
+
+/// Whether `name` names a dreamfile, with or without its '.zzz.yaml'.
+pub fn dreamfile_exists(name: &str) -> bool {
+    let path = Path::new(name);
+    is_dreamfile(path)
+        || [".zzz.yml", ".zzz.yaml"]
+            .iter()
+            .any(|ext| Path::new(&format!("{}{}", name, ext)).is_file())
+}
+
+/// Finds the dreamfile to use when none was given, searching the current
+/// directory and then its parents. Returns it without its '.zzz.yaml'.
+pub fn discover_dreamfile(caller: Cmd) -> String {
+    let dir = match env::current_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            throw_fatal(format!("Very Bad: {e}").as_str());
+            quit(4);
+            return String::new();
         }
-    } else {
-        usage_and_quit(caller.name, "Not Enough Argumets!");
-        Err((
-            "Not enough Arguments!".to_string(),
-            "Invalid Args".to_string(),
-        ))
+    };
+    let paths = find_dreamfiles(&dir);
+    match paths.len() {
+        0 => {
+            errprint!("Missing Filename! (no dreamfile here or in any parent directory)");
+            usage(caller.name);
+            quit(2);
+            String::new()
+        }
+        1 => dreamfile_stem(&paths[0]),
+        _ => match print_file_list(0) {
+            Ok(res) => res.2,
+            Err(..) => {
+                quit(1);
+                String::new()
+            }
+        },
     }
 }
 
@@ -349,17 +389,11 @@ pub fn print_file_list(way: usize) -> Result<(char, Vec<String>, String), Box<dy
     if way == 0 {
         match env::current_dir() {
             Ok(dir) => {
-                match crate::helper::resource::get_yaml_paths(dir.into_os_string().into_string().unwrap().as_str()) {
+                match get_dreamfile_paths(&dir) {
                     Ok(paths) => {
                         let paths_f: Vec<String> = paths
                             .into_iter()
-                            .map(|s| {
-                                s.file_stem()
-                                    .unwrap()
-                                    .to_str()
-                                    .map(|s| s.to_string())
-                                    .unwrap()
-                            })
+                            .map(|s| s.with_extension("").to_string_lossy().to_string())
                             .collect();
                         let index = option_list("info", paths_f.clone(), "Choose a file (0 to quit):");
                         let index_c = index[0];
@@ -396,17 +430,11 @@ pub fn print_file_list(way: usize) -> Result<(char, Vec<String>, String), Box<dy
     } else if way == 1 {
         match env::current_dir() {
             Ok(dir) => {
-                match crate::helper::resource::get_yaml_paths(dir.into_os_string().into_string().unwrap().as_str()) {
+                match get_dreamfile_paths(&dir) {
                     Ok(paths) => {
                         let paths_f: Vec<String> = paths
                             .into_iter()
-                            .map(|s| {
-                                s.file_stem()
-                                    .unwrap()
-                                    .to_str()
-                                    .map(|s| s.to_string())
-                                    .unwrap()
-                            })
+                            .map(|s| s.with_extension("").to_string_lossy().to_string())
                             .collect();
                         let index = option_list("info", paths_f.clone(), "Choose a file (0 to quit):");
                         let index_c = index[0];
@@ -440,6 +468,22 @@ pub fn print_file_list(way: usize) -> Result<(char, Vec<String>, String), Box<dy
     
 }
 
+/// Splits 'name@1.2.3' into its name and (semver checked) version.
+pub fn parse_tool_spec(spec: &str) -> Result<(String, Option<String>), String> {
+    match spec.split_once('@') {
+        None => Ok((spec.to_string(), None)),
+        Some((name, version)) => {
+            if name.is_empty() {
+                return Err(format!("Missing tool name in '{}'", spec));
+            }
+            match semver::Version::parse(version.strip_prefix('v').unwrap_or(version)) {
+                Ok(..) => Ok((name.to_string(), Some(version.to_string()))),
+                Err(e) => Err(format!("Invalid version '{0}' for '{1}': {2}", version, name, e)),
+            }
+        }
+    }
+}
+
 pub fn argparse(argsv: &[String], pos: usize, cmd: Cmd) -> bool {
     // Parse arguments
     cmd.aliases.contains(&argsv[pos].as_str())
@@ -471,25 +515,39 @@ pub fn verbose_check(global_opts: &[bool]) -> bool {
     }
 }
 
-pub fn verbose_set_true(argsv: &[String], global_opts: &mut Vec<bool>) -> Vec<bool> {
+pub fn verbose_set_true(argsv: &[String], global_opts: &mut [bool]) -> Vec<bool> {
     if argsv.contains(&"-v".to_string()) {
-        global_opts.insert(0, true);
+        global_opts[0] = true;
         global_opts.to_vec()
     } else {
         global_opts.to_vec()
     }
 }
 
-pub fn force_set_true(argsv: &[String], global_opts: &mut Vec<bool>) -> Vec<bool> {
+pub fn force_set_true(argsv: &[String], global_opts: &mut [bool]) -> Vec<bool> {
     if argsv.contains(&"-f".to_string()) {
-        global_opts.insert(1, true);
+        global_opts[1] = true;
         global_opts.to_vec()
     } else {
         global_opts.to_vec()
     }
 }
 
-pub fn scan_flags(argsv: &[String], global_opts: &mut Vec<bool>) -> Vec<bool> {
+pub fn offline_check(global_opts: &[bool]) -> bool {
+    global_opts.get(3).copied().unwrap_or(false)
+}
+
+/// '--offline', or ZZZ_OFFLINE set to anything but '' and '0'.
+pub fn offline_set_true(argsv: &[String], global_opts: &mut [bool]) -> Vec<bool> {
+    let from_env = env::var("ZZZ_OFFLINE").is_ok_and(|v| !v.is_empty() && v != "0");
+    if argsv.contains(&"--offline".to_string()) || from_env {
+        global_opts[3] = true;
+    }
+    global_opts.to_vec()
+}
+
+pub fn scan_flags(argsv: &[String], global_opts: &mut [bool]) -> Vec<bool> {
+    offline_set_true(argsv, global_opts);
     let dream_flags: Vec<&str> = vec!["-v", "-f"];
     for i in dream_flags {
         if argsv.contains(&i.to_owned().to_string()) {
@@ -509,29 +567,54 @@ pub fn scan_flags(argsv: &[String], global_opts: &mut Vec<bool>) -> Vec<bool> {
     global_opts.to_vec()
 }
 
-pub fn get_yaml_paths(dir: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
-    let paths = std::fs::read_dir(dir)?
-        // Filter out all those directory entries which couldn't be read
-        .filter_map(|res| res.ok())
-        // Map the directory entries to paths
-        .map(|dir_entry| dir_entry.path())
-        // Filter out all paths with extensions other than .yaml or .yml
-        .filter_map(|path| {
-            if path
-                .extension()
-                .map_or(false, |ext| (ext == "yaml") || ext == "yml")
-            {
-                Some(path)
-            } else {
-                None
-            }
-        })
-        .collect::<Vec<_>>();
+fn is_dreamfile(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .file_name()
+            .and_then(|f| f.to_str())
+            .is_some_and(|f| f.ends_with(".zzz.yaml") || f.ends_with(".zzz.yml"))
+}
+
+/// Dreamfiles of the closest directory (`start` or one of its parents)
+/// having any, relative to `start`.
+pub fn find_dreamfiles(start: &Path) -> Vec<PathBuf> {
+    for (depth, dir) in start.ancestors().enumerate() {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|res| res.ok())
+            .map(|dir_entry| dir_entry.path())
+            .filter(|path| is_dreamfile(path))
+            .map(|path| {
+                let up: PathBuf = std::iter::repeat_n("..", depth).collect();
+                up.join(path.file_name().unwrap())
+            })
+            .collect();
+        if !paths.is_empty() {
+            paths.sort();
+            return paths;
+        }
+    }
+    vec![]
+}
+
+/// '../foo.zzz.yaml' -> '../foo'
+pub fn dreamfile_stem(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    path.strip_suffix(".zzz.yaml")
+        .or_else(|| path.strip_suffix(".zzz.yml"))
+        .unwrap_or(&path)
+        .to_string()
+}
+
+pub fn get_dreamfile_paths(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let paths = find_dreamfiles(dir);
     if !paths.is_empty() {
         Ok(paths)
     } else {
         let dummy: Vec<bool> = vec![false];
-        NOFILESERROR.show_error(&"dummy".to_string(), &dummy);
+        NOFILESERROR.show_error("dummy", &dummy);
         Err("No files".into())
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,8 @@
 /// Primary Logic for the Shell Interceptor
-use super::{clear_term, colored::Colorize, resource::quit, SELF_VERSION};
+use super::{
+    clear_term, colored::Colorize, environment::EnvMap, home::DreamerHome, resource::quit,
+    SELF_VERSION,
+};
 
 use std::{
     env::{self},
@@ -15,7 +18,12 @@ fn zzzsh_check_is_local(cmd: &str, env_cmds: &[String]) -> bool {
     env_cmds.contains(&cmd.to_string())
 }
 
-fn zzsh_loop(env_cmds: Vec<String>, home_dir: Result<String, env::VarError>, hashname: u64) {
+fn zzsh_loop(
+    env_cmds: Vec<String>,
+    home_dir: Result<DreamerHome, String>,
+    hashname: String,
+    env: EnvMap,
+) {
     loop {
         let curr_dir = env::current_dir();
         shellprint!("(~{}) [zzz] @> ", curr_dir.unwrap().to_string_lossy());
@@ -60,15 +68,13 @@ fn zzsh_loop(env_cmds: Vec<String>, home_dir: Result<String, env::VarError>, has
                                 Stdio::inherit()
                             };
 
-                            let cmd_local = format!(
-                                "{0}/.dreamer/bins/{1}/{2}",
-                                home_dir.clone().unwrap(),
-                                hashname,
-                                command
-                            );
-                            println!("{}", cmd_local);
+                            let cmd_local = match &home_dir {
+                                Ok(home) => home.project_bins(&hashname).join(command),
+                                Err(..) => Path::new(command).to_path_buf(),
+                            };
                             let output = Command::new(cmd_local)
                                 .args(args)
+                                .envs(&env)
                                 .stdin(stdin)
                                 .stdout(stdout)
                                 .spawn();
@@ -95,6 +101,7 @@ fn zzsh_loop(env_cmds: Vec<String>, home_dir: Result<String, env::VarError>, has
 
                             let output = Command::new(command)
                                 .args(args)
+                                .envs(&env)
                                 .stdin(stdin)
                                 .stdout(stdout)
                                 .spawn();
@@ -119,10 +126,15 @@ fn zzsh_loop(env_cmds: Vec<String>, home_dir: Result<String, env::VarError>, has
         }
     }
 }
-pub fn init_shell(env_cmds: Vec<String>, home_dir: Result<String, env::VarError>, hashname: u64) {
+pub fn init_shell(
+    env_cmds: Vec<String>,
+    home_dir: Result<DreamerHome, String>,
+    hashname: String,
+    env: EnvMap,
+) {
     infoprint!("Counting Sheep...");
     //pause();
     //clear_term();
     infoprint!("Dreamer {0} (type 'exit()' to exit)", SELF_VERSION);
-    zzsh_loop(env_cmds, home_dir, hashname);
+    zzsh_loop(env_cmds, home_dir, hashname, env);
 }
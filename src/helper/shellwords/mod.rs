@@ -0,0 +1,235 @@
+/// POSIX style splitting of task commands into words.
+// std imports
+use std::{error::Error, fmt};
+
+#[derive(Debug, PartialEq)]
+pub enum SplitError {
+    UnterminatedQuote(char),
+    TrailingBackslash,
+    /// An unquoted shell operator ('|', '&&', '>'...), which only means
+    /// something when the command goes through a shell.
+    Operator(char),
+}
+
+impl fmt::Display for SplitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SplitError::UnterminatedQuote(q) => write!(f, "Unterminated {} quote", q),
+            SplitError::TrailingBackslash => write!(f, "Trailing backslash"),
+            SplitError::Operator(c) => write!(
+                f,
+                "'{}' needs a shell, use 'shell: true' or an 'sh:' prefix for this command",
+                c
+            ),
+        }
+    }
+}
+
+impl Error for SplitError {}
+
+/// A command split into its leading 'NAME=value' assignments and its argv.
+/// A '$' that was quoted or escaped is doubled in them, so that expanding
+/// the words keeps it as is.
+#[derive(Debug, PartialEq)]
+pub struct ParsedCommand {
+    pub env: Vec<(String, String)>,
+    pub argv: Vec<String>,
+}
+
+/// Splits `line` into words, honouring single quotes, double quotes and
+/// backslash escapes the way a POSIX shell does (without any expansion).
+pub fn split(line: &str) -> Result<Vec<String>, SplitError> {
+    tokenize(line, false)
+}
+
+/// Pushes a '$' that must not be expanded, doubled when `escape_dollars`.
+fn push_literal(word: &mut String, c: char, escape_dollars: bool) {
+    if c == '$' && escape_dollars {
+        word.push('$');
+    }
+    word.push(c);
+}
+
+fn tokenize(line: &str, escape_dollars: bool) -> Result<Vec<String>, SplitError> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => push_literal(&mut word, c, escape_dollars),
+                        None => return Err(SplitError::UnterminatedQuote('\'')),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('$' | '`' | '"' | '\\')) => {
+                                push_literal(&mut word, c, escape_dollars)
+                            }
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(SplitError::UnterminatedQuote('"')),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(SplitError::UnterminatedQuote('"')),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(c) => {
+                    in_word = true;
+                    push_literal(&mut word, c, escape_dollars);
+                }
+                None => return Err(SplitError::TrailingBackslash),
+            },
+            '|' | '&' | ';' | '<' | '>' | '(' | ')' => return Err(SplitError::Operator(c)),
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+fn is_assignment(word: &str) -> Option<(String, String)> {
+    let (name, value) = word.split_once('=')?;
+    let mut chars = name.chars();
+    let first = chars.next()?;
+    if (first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        Some((name.to_string(), value.to_string()))
+    } else {
+        None
+    }
+}
+
+/// Splits `line` and peels off its leading 'NAME=value' words, keeping
+/// the words ready for expansion.
+pub fn parse_command(line: &str) -> Result<ParsedCommand, SplitError> {
+    let words = tokenize(line, true)?;
+    let mut env = vec![];
+    let mut rest = words.into_iter().peekable();
+    while let Some(assignment) = rest.peek().and_then(|w| is_assignment(w)) {
+        env.push(assignment);
+        rest.next();
+    }
+    Ok(ParsedCommand {
+        env,
+        argv: rest.collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        split(line).unwrap()
+    }
+
+    #[test]
+    fn splits_on_blanks() {
+        assert_eq!(words("  echo  a\tb\nc "), ["echo", "a", "b", "c"]);
+        assert!(words("").is_empty());
+        assert!(words("   ").is_empty());
+    }
+
+    #[test]
+    fn single_quotes_are_literal() {
+        assert_eq!(words(r#"echo 'a b' '\n' '"' '$x'"#), ["echo", "a b", r"\n", "\"", "$x"]);
+        assert_eq!(words("''"), [""]);
+    }
+
+    #[test]
+    fn double_quotes_escape_few_chars() {
+        assert_eq!(words(r#"echo "a b" "\"\\\$" "\n""#), ["echo", "a b", "\"\\$", r"\n"]);
+        assert_eq!(words("\"a\\\nb\""), ["ab"]);
+        assert_eq!(words(r#""""#), [""]);
+    }
+
+    #[test]
+    fn quotes_join_words() {
+        assert_eq!(words(r#"a'b c'"d e"f"#), ["ab cd ef"]);
+    }
+
+    #[test]
+    fn backslash_escapes() {
+        assert_eq!(words(r"a\ b \'c \|"), ["a b", "'c", "|"]);
+        assert_eq!(words("a\\\nb"), ["ab"]);
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert_eq!(split("echo 'a"), Err(SplitError::UnterminatedQuote('\'')));
+        assert_eq!(split("echo \"a"), Err(SplitError::UnterminatedQuote('"')));
+        assert_eq!(split("echo a\\"), Err(SplitError::TrailingBackslash));
+    }
+
+    #[test]
+    fn rejects_unquoted_operators() {
+        for (line, op) in [
+            ("a | b", '|'),
+            ("a && b", '&'),
+            ("a; b", ';'),
+            ("a > f", '>'),
+            ("a < f", '<'),
+            ("(a)", '('),
+        ] {
+            assert_eq!(split(line), Err(SplitError::Operator(op)), "{}", line);
+        }
+        assert_eq!(words(r#"echo '|' "&&" \;"#), ["echo", "|", "&&", ";"]);
+    }
+
+    #[test]
+    fn peels_leading_assignments() {
+        let parsed = parse_command("A=1 _B='x y' cmd C=2").unwrap();
+        assert_eq!(
+            parsed.env,
+            [("A".to_string(), "1".to_string()), ("_B".to_string(), "x y".to_string())]
+        );
+        assert_eq!(parsed.argv, ["cmd", "C=2"]);
+    }
+
+    #[test]
+    fn doubles_quoted_dollars_for_expansion() {
+        let parsed = parse_command(r#"A='$x' echo $y "$z" '$w' \$v "\$u""#).unwrap();
+        assert_eq!(parsed.env, [("A".to_string(), "$$x".to_string())]);
+        assert_eq!(parsed.argv, ["echo", "$y", "$z", "$$w", "$$v", "$$u"]);
+        assert_eq!(words(r"'$w' \$v"), ["$w", "$v"]);
+    }
+
+    #[test]
+    fn only_names_are_assignments() {
+        let parsed = parse_command("1A=x =y a-b=z cmd").unwrap();
+        assert!(parsed.env.is_empty());
+        assert_eq!(parsed.argv, ["1A=x", "=y", "a-b=z", "cmd"]);
+        let parsed = parse_command("A=1").unwrap();
+        assert_eq!(parsed.env.len(), 1);
+        assert!(parsed.argv.is_empty());
+    }
+}
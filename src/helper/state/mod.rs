@@ -0,0 +1,55 @@
+/// Per-machine load state, kept in state/<project id>.yaml under the zzz home.
+// Extern imports
+use serde::{Deserialize, Serialize};
+
+// std imports
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::digest::sha256_file;
+use super::home::DreamerHome;
+
+/// What a project looked like the last time it was loaded on this machine.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct LoadState {
+    pub dreamfile: String,
+    pub lock: Option<String>,
+}
+
+impl LoadState {
+    /// Snapshots the current dreamfile and lockfile contents.
+    pub fn current(filepath: &str, lockpath: &Path) -> Result<LoadState, Box<dyn Error>> {
+        let lock = if lockpath.exists() {
+            Some(sha256_file(lockpath)?)
+        } else {
+            None
+        };
+        Ok(LoadState {
+            dreamfile: sha256_file(Path::new(filepath))?,
+            lock,
+        })
+    }
+}
+
+fn state_path(home: &DreamerHome, id: &str) -> PathBuf {
+    home.state().join(format!("{}.yaml", id))
+}
+
+pub fn read_state(home: &DreamerHome, id: &str) -> Option<LoadState> {
+    let body = fs::read_to_string(state_path(home, id)).ok()?;
+    serde_yaml::from_str(&body).ok()
+}
+
+pub fn write_state(home: &DreamerHome, id: &str, state: &LoadState) -> Result<(), Box<dyn Error>> {
+    let path = state_path(home, id);
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, serde_yaml::to_string(state)?)?;
+    Ok(())
+}
+
+pub fn clear_state(home: &DreamerHome, id: &str) {
+    let _ = fs::remove_file(state_path(home, id));
+}
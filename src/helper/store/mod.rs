@@ -0,0 +1,68 @@
+/// Content-addressed store of installed tools, shared by every project.
+///
+/// The executables unpacked from a download live in 'store/<sha256>/', keyed
+/// by the digest of the download, and project bin dirs only link to them.
+// std imports
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::home::DreamerHome;
+
+/// Where the executables of the download with digest `sha256` are kept.
+pub fn artifact_dir(home: &DreamerHome, sha256: &str) -> PathBuf {
+    home.store().join(sha256.to_ascii_lowercase())
+}
+
+/// Whether the store already has every one of `bins` for `sha256`.
+pub fn contains(home: &DreamerHome, sha256: &str, bins: &[String]) -> bool {
+    let dir = artifact_dir(home, sha256);
+    !bins.is_empty() && bins.iter().all(|b| dir.join(b).is_file())
+}
+
+/// A scratch dir in the store, on the same filesystem so that what's put
+/// in it can be renamed into place.
+pub fn staging_dir(home: &DreamerHome, name: &str) -> io::Result<PathBuf> {
+    let dir = home
+        .store()
+        .join(format!(".staging-{0}-{1}", std::process::id(), name));
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Moves `bins` from `staging` into the store under `sha256`, keeping any
+/// copy that is already there, and removes `staging`.
+pub fn commit(
+    home: &DreamerHome,
+    sha256: &str,
+    staging: &Path,
+    bins: &[String],
+) -> io::Result<PathBuf> {
+    let dir = artifact_dir(home, sha256);
+    fs::create_dir_all(&dir)?;
+    for bin in bins {
+        let target = dir.join(bin);
+        if !target.is_file() {
+            fs::rename(staging.join(bin), &target)?;
+        }
+    }
+    let _ = fs::remove_dir_all(staging);
+    Ok(dir)
+}
+
+/// Makes `dest` point at `src`: a symlink where possible, else a hardlink,
+/// else a copy. Whatever was at `dest` is replaced.
+pub fn link(src: &Path, dest: &Path) -> io::Result<()> {
+    let file_name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = dest.with_file_name(format!(".{0}.{1}.link", file_name, std::process::id()));
+    let _ = fs::remove_file(&tmp);
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(src, &tmp);
+    #[cfg(not(unix))]
+    let linked: io::Result<()> = Err(io::ErrorKind::Unsupported.into());
+    if linked.is_err() && fs::hard_link(src, &tmp).is_err() {
+        fs::copy(src, &tmp)?;
+    }
+    fs::rename(&tmp, dest)
+}
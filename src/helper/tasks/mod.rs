@@ -0,0 +1,98 @@
+/// Ordering of tasks from their 'depends_on' lists.
+// Extern imports
+use indexmap::IndexMap;
+
+// std imports
+use std::error::Error;
+
+use super::Task;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    Visiting,
+    Done,
+}
+
+fn visit<'a>(
+    tasks: &'a IndexMap<String, Task>,
+    name: &'a str,
+    marks: &mut IndexMap<&'a str, Mark>,
+    path: &mut Vec<&'a str>,
+    order: &mut Vec<String>,
+) -> Result<(), Box<dyn Error>> {
+    match marks.get(name) {
+        Some(Mark::Done) => return Ok(()),
+        Some(Mark::Visiting) => {
+            let start = path.iter().position(|n| *n == name).unwrap();
+            let mut cycle: Vec<&str> = path[start..].to_vec();
+            cycle.push(name);
+            return Err(format!("Task dependency cycle: {}", cycle.join(" -> ")).into());
+        }
+        None => {}
+    }
+    let task = tasks.get(name).unwrap();
+    marks.insert(name, Mark::Visiting);
+    path.push(name);
+    for dep in &task.depends_on {
+        if !tasks.contains_key(dep) {
+            return Err(format!("Task '{0}' depends on unknown task '{1}'", name, dep).into());
+        }
+        visit(tasks, dep, marks, path, order)?;
+    }
+    path.pop();
+    marks.insert(name, Mark::Done);
+    order.push(name.to_string());
+    Ok(())
+}
+
+/// Returns `target` and everything it depends on, each task once, in an
+/// order where every task comes after its dependencies.
+pub fn plan(tasks: &IndexMap<String, Task>, target: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut order = vec![];
+    visit(tasks, target, &mut IndexMap::new(), &mut vec![], &mut order)?;
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tasks(src: &str) -> IndexMap<String, Task> {
+        serde_yaml::from_str(src).unwrap()
+    }
+
+    #[test]
+    fn dependencies_come_first() {
+        let tasks = tasks(
+            "build: {run: [make], depends_on: [fetch, gen]}\nfetch: [get]\ngen: {run: [gen], depends_on: [fetch]}\ntest: {run: [t], depends_on: [build]}\n",
+        );
+        assert_eq!(plan(&tasks, "test").unwrap(), ["fetch", "gen", "build", "test"]);
+        assert_eq!(plan(&tasks, "gen").unwrap(), ["fetch", "gen"]);
+    }
+
+    #[test]
+    fn shared_dependencies_run_once() {
+        let tasks = tasks(
+            "a: [a]\nb: {run: [b], depends_on: [a]}\nc: {run: [c], depends_on: [a]}\nall: {run: [], depends_on: [b, c, a]}\n",
+        );
+        assert_eq!(plan(&tasks, "all").unwrap(), ["a", "b", "c", "all"]);
+    }
+
+    #[test]
+    fn reports_cycles() {
+        let tasks = tasks(
+            "a: {run: [a], depends_on: [b]}\nb: {run: [b], depends_on: [c]}\nc: {run: [c], depends_on: [a]}\n",
+        );
+        let err = plan(&tasks, "a").unwrap_err().to_string();
+        assert_eq!(err, "Task dependency cycle: a -> b -> c -> a");
+        let tasks = self::tasks("a: {run: [a], depends_on: [a]}\n");
+        assert_eq!(plan(&tasks, "a").unwrap_err().to_string(), "Task dependency cycle: a -> a");
+    }
+
+    #[test]
+    fn reports_unknown_dependencies() {
+        let tasks = tasks("a: {run: [a], depends_on: [nope]}\n");
+        let err = plan(&tasks, "a").unwrap_err().to_string();
+        assert_eq!(err, "Task 'a' depends on unknown task 'nope'");
+    }
+}
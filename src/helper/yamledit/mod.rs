@@ -0,0 +1,468 @@
+/// Format preserving edits of the 'deps.tools' list of a dreamfile.
+///
+/// Only the lines of the entry being added or removed are touched; the rest
+/// of the document (comments, key order, quoting...) is kept byte-for-byte.
+/// Every edit is parsed back, and refused unless it gives the tools asked for.
+// std imports
+use std::{
+    error::Error,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use super::{Tool, ZzzConfig};
+
+struct Line<'a> {
+    text: &'a str,
+    indent: usize,
+    content: &'a str,
+}
+
+impl Line<'_> {
+    fn is_meaningful(&self) -> bool {
+        !self.content.is_empty() && !self.content.starts_with('#')
+    }
+
+    fn is_item(&self) -> bool {
+        self.content == "-" || self.content.starts_with("- ")
+    }
+}
+
+fn split_lines(src: &str) -> Vec<Line<'_>> {
+    src.split_inclusive('\n')
+        .map(|text| {
+            let body = text.trim_end_matches(['\n', '\r']);
+            let content = body.trim_start_matches(' ');
+            Line {
+                text,
+                indent: body.len() - content.len(),
+                content: content.trim_end(),
+            }
+        })
+        .collect()
+}
+
+/// Returns the value of `key: value` (comments stripped), if `content` is that key.
+fn key_value<'a>(content: &'a str, key: &str) -> Option<&'a str> {
+    let rest = content.strip_prefix(key)?.trim_start_matches(' ');
+    let value = rest.strip_prefix(':')?;
+    if !(value.is_empty() || value.starts_with(' ')) {
+        return None;
+    }
+    let value = match value.find(" #") {
+        Some(i) => &value[..i],
+        None => value,
+    };
+    Some(value.trim())
+}
+
+/// Where the 'deps.tools' sequence lives in the document.
+enum ToolsSeq {
+    /// There is no 'deps' key at all.
+    NoDeps,
+    /// 'deps' exists, without a 'tools' key. Holds the 'deps' line and
+    /// the indent of its children.
+    NoTools(usize, usize),
+    /// 'tools: []'. Holds the 'tools' line.
+    FlowEmpty(usize),
+    /// A block sequence (possibly without items yet).
+    Block {
+        item_indent: usize,
+        /// (first line, one past the last meaningful line) of every item
+        items: Vec<(usize, usize)>,
+        /// One past the last meaningful line of the sequence
+        end: usize,
+    },
+}
+
+fn find_tools(lines: &[Line]) -> Result<ToolsSeq, Box<dyn Error>> {
+    let Some(deps) = lines
+        .iter()
+        .position(|l| l.indent == 0 && key_value(l.content, "deps").is_some())
+    else {
+        return Ok(ToolsSeq::NoDeps);
+    };
+    if !key_value(lines[deps].content, "deps").unwrap().is_empty() {
+        return Err("'deps' is not a block mapping, edit it by hand".into());
+    }
+    let deps_end = (deps + 1..lines.len())
+        .find(|&i| lines[i].indent == 0 && lines[i].is_meaningful())
+        .unwrap_or(lines.len());
+    let child_indent = (deps + 1..deps_end)
+        .filter(|&i| lines[i].is_meaningful())
+        .map(|i| lines[i].indent)
+        .min()
+        .unwrap_or(2);
+    let Some(header) = (deps + 1..deps_end).find(|&i| {
+        lines[i].indent == child_indent && key_value(lines[i].content, "tools").is_some()
+    }) else {
+        return Ok(ToolsSeq::NoTools(deps, child_indent));
+    };
+    match key_value(lines[header].content, "tools").unwrap() {
+        "" | "~" | "null" => {}
+        "[]" => return Ok(ToolsSeq::FlowEmpty(header)),
+        _ => return Err("'deps.tools' is not a block sequence, edit it by hand".into()),
+    }
+
+    let mut items: Vec<(usize, usize)> = vec![];
+    let mut item_indent = None;
+    let mut end = header + 1;
+    for (i, line) in lines.iter().enumerate().skip(header + 1) {
+        if !line.is_meaningful() {
+            continue;
+        }
+        let in_seq = line.indent > child_indent || (line.indent == child_indent && line.is_item());
+        if !in_seq {
+            break;
+        }
+        if line.is_item() && item_indent.is_none_or(|n| n == line.indent) {
+            item_indent = Some(line.indent);
+            items.push((i, i + 1));
+        }
+        if let Some(last) = items.last_mut() {
+            last.1 = i + 1;
+        }
+        end = i + 1;
+    }
+    Ok(ToolsSeq::Block {
+        item_indent: item_indent.unwrap_or(child_indent),
+        items,
+        end,
+    })
+}
+
+fn newline_of(src: &str) -> &'static str {
+    if src.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+/// Renders `tool` as a sequence item at `indent`.
+fn render_item(tool: &Tool, indent: usize, nl: &str) -> Result<String, Box<dyn Error>> {
+    let body = serde_yaml::to_string(tool)?;
+    let pad = " ".repeat(indent);
+    let mut out = String::new();
+    for (i, line) in body.lines().enumerate() {
+        let marker = if i == 0 { "- " } else { "  " };
+        out.push_str(&format!("{0}{1}{2}{3}", pad, marker, line, nl));
+    }
+    Ok(out)
+}
+
+fn splice(lines: &[Line], at: usize, remove: usize, insert: &str, nl: &str) -> String {
+    let mut out = String::new();
+    for line in &lines[..at] {
+        out.push_str(line.text);
+    }
+    if !insert.is_empty() && !out.is_empty() && !out.ends_with('\n') {
+        out.push_str(nl);
+    }
+    out.push_str(insert);
+    for line in &lines[(at + remove).min(lines.len())..] {
+        out.push_str(line.text);
+    }
+    out
+}
+
+/// The tool described by the item spanning `lines[start..end]`.
+fn parse_item(lines: &[Line], start: usize, end: usize) -> Option<Tool> {
+    let indent = lines[start].indent;
+    let text: String = lines[start..end]
+        .iter()
+        .enumerate()
+        .map(|(i, l)| {
+            let body = l.text.get(indent.min(l.indent)..).unwrap_or("");
+            if i == 0 {
+                format!(" {}", &body[1..])
+            } else {
+                body.to_string()
+            }
+        })
+        .collect();
+    serde_yaml::from_str(&text).ok()
+}
+
+fn find_item(lines: &[Line], seq: &ToolsSeq, name: &str) -> Option<(usize, usize)> {
+    match seq {
+        ToolsSeq::Block { items, .. } => items
+            .iter()
+            .copied()
+            .find(|&(start, end)| parse_item(lines, start, end).is_some_and(|t| t.name == name)),
+        _ => None,
+    }
+}
+
+/// A tool's name and version.
+type ToolVersion = (String, Option<String>);
+
+/// Names and versions of the tools of a dreamfile, in order.
+fn tool_versions(src: &str) -> Result<Vec<ToolVersion>, Box<dyn Error>> {
+    let config: ZzzConfig = serde_yaml::from_str(src)?;
+    Ok(config
+        .deps
+        .tools
+        .into_iter()
+        .map(|t| (t.name, t.version))
+        .collect())
+}
+
+/// Returns `edited` if it is a valid dreamfile with exactly the `expected`
+/// tools.
+fn checked(edited: String, expected: &[ToolVersion]) -> Result<String, Box<dyn Error>> {
+    match tool_versions(&edited) {
+        Ok(tools) if tools == expected => Ok(edited),
+        Ok(_) => Err("the edit doesn't give the expected tools, edit it by hand".into()),
+        Err(e) => Err(format!("the edit gives an invalid dreamfile ({}), edit it by hand", e).into()),
+    }
+}
+
+/// Appends `tool` to 'deps.tools'.
+pub fn insert_tool(src: &str, tool: &Tool) -> Result<String, Box<dyn Error>> {
+    let mut expected = tool_versions(src)?;
+    expected.push((tool.name.clone(), tool.version.clone()));
+    let lines = split_lines(src);
+    let nl = newline_of(src);
+    let out = match find_tools(&lines)? {
+        ToolsSeq::NoDeps => {
+            let item = render_item(tool, 2, nl)?;
+            splice(&lines, lines.len(), 0, &format!("deps:{0}  tools:{0}{1}", nl, item), nl)
+        }
+        ToolsSeq::NoTools(deps, indent) => {
+            let item = render_item(tool, indent, nl)?;
+            let header = format!("{0}tools:{1}", " ".repeat(indent), nl);
+            splice(&lines, deps + 1, 0, &format!("{}{}", header, item), nl)
+        }
+        ToolsSeq::FlowEmpty(header) => {
+            let indent = lines[header].indent;
+            let item = render_item(tool, indent, nl)?;
+            let header_text = format!("{0}tools:{1}", " ".repeat(indent), nl);
+            splice(&lines, header, 1, &format!("{}{}", header_text, item), nl)
+        }
+        ToolsSeq::Block {
+            item_indent, end, ..
+        } => splice(&lines, end, 0, &render_item(tool, item_indent, nl)?, nl),
+    };
+    checked(out, &expected)
+}
+
+/// Removes the entry named `name` from 'deps.tools'.
+pub fn remove_tool(src: &str, name: &str) -> Result<String, Box<dyn Error>> {
+    let mut expected = tool_versions(src)?;
+    expected.retain(|(n, _)| n != name);
+    let lines = split_lines(src);
+    let seq = find_tools(&lines)?;
+    match find_item(&lines, &seq, name) {
+        Some((start, end)) => checked(
+            splice(&lines, start, end - start, "", newline_of(src)),
+            &expected,
+        ),
+        None => Err(format!("'{}' is not in deps.tools", name).into()),
+    }
+}
+
+/// Sets the 'version' of the entry named `name`, adding the key if needed.
+pub fn set_tool_version(src: &str, name: &str, version: &str) -> Result<String, Box<dyn Error>> {
+    let mut expected = tool_versions(src)?;
+    for (n, v) in expected.iter_mut() {
+        if n == name {
+            *v = Some(version.to_string());
+        }
+    }
+    checked(edit_version(src, name, version)?, &expected)
+}
+
+fn edit_version(src: &str, name: &str, version: &str) -> Result<String, Box<dyn Error>> {
+    let lines = split_lines(src);
+    let nl = newline_of(src);
+    let seq = find_tools(&lines)?;
+    let Some((start, end)) = find_item(&lines, &seq, name) else {
+        return Err(format!("'{}' is not in deps.tools", name).into());
+    };
+    let key_indent = lines[start].indent + 2;
+    let value = serde_yaml::to_string(version)?;
+    let value = value.trim_end();
+    // The first key shares its line with the '- ' marker
+    let first = &lines[start].content[1..].trim_start();
+    if first.starts_with('{') {
+        return Err(format!("'{}' is a flow mapping, edit it by hand", name).into());
+    }
+    if key_value(first, "version").is_some() {
+        let text = format!("{0}- version: {1}{2}", " ".repeat(lines[start].indent), value, nl);
+        return Ok(splice(&lines, start, 1, &text, nl));
+    }
+    let text = format!("{0}version: {1}{2}", " ".repeat(key_indent), value, nl);
+    match (start + 1..end)
+        .find(|&i| lines[i].indent == key_indent && key_value(lines[i].content, "version").is_some())
+    {
+        Some(i) => Ok(splice(&lines, i, 1, &text, nl)),
+        None => Ok(splice(&lines, start + 1, 0, &text, nl)),
+    }
+}
+
+/// Replaces `path` with `contents` through a temporary file and a rename,
+/// so readers never see a half written dreamfile.
+pub fn write_atomic(path: &Path, contents: &str) -> Result<(), Box<dyn Error>> {
+    let file_name = path.file_name().ok_or("Invalid path")?.to_string_lossy();
+    let tmp: PathBuf = path.with_file_name(format!(".{0}.{1}.tmp", file_name, std::process::id()));
+    let written = fs::File::create(&tmp).and_then(|mut f| {
+        f.write_all(contents.as_bytes())?;
+        if let Ok(meta) = fs::metadata(path) {
+            f.set_permissions(meta.permissions())?;
+        }
+        f.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    const HEAD: &str = "project:\n  name: p\n  description: p\n  version: 0.0.0\ndo:\n  run:\n    - a\n";
+
+    fn doc(deps: &str) -> String {
+        format!("{}{}", HEAD, deps)
+    }
+
+    fn tool(name: &str, version: Option<&str>) -> Tool {
+        Tool {
+            name: name.to_string(),
+            version: version.map(str::to_string),
+            link: format!("https://example.com/{}", name),
+            links: BTreeMap::new(),
+            sha256: None,
+            sha512: None,
+            bin: vec![],
+            strip_components: 0,
+        }
+    }
+
+    fn names(src: &str) -> Vec<ToolVersion> {
+        tool_versions(src).unwrap()
+    }
+
+    fn entry(name: &str, version: Option<&str>) -> ToolVersion {
+        (name.to_string(), version.map(str::to_string))
+    }
+
+    const BLOCK: &str = "deps:\n  tools:\n    - name: a\n      link: https://example.com/a\n    - name: b\n      version: \"1.0\"\n      link: https://example.com/b\n";
+    const FLOW: &str = "deps:\n  tools:\n    - {name: a, link: https://example.com/a}\n    - {name: b, link: https://example.com/b}\n";
+    const COMMENTED: &str = "# tools we need\ndeps:\n  # pinned by hand\n  tools:\n    - name: a # the first one\n      link: https://example.com/a\n    # b is next\n    - name: b\n      link: https://example.com/b\n# trailing\n";
+
+    #[test]
+    fn insert_into_block() {
+        let out = insert_tool(&doc(BLOCK), &tool("c", Some("2"))).unwrap();
+        assert!(out.starts_with(&doc(BLOCK)));
+        assert_eq!(
+            names(&out),
+            vec![entry("a", None), entry("b", Some("1.0")), entry("c", Some("2"))]
+        );
+    }
+
+    #[test]
+    fn insert_into_flow_items() {
+        let out = insert_tool(&doc(FLOW), &tool("c", None)).unwrap();
+        assert!(out.starts_with(&doc(FLOW)));
+        assert_eq!(names(&out), vec![entry("a", None), entry("b", None), entry("c", None)]);
+    }
+
+    #[test]
+    fn insert_without_deps() {
+        let out = insert_tool(&doc(""), &tool("a", None)).unwrap();
+        assert!(out.starts_with(HEAD));
+        assert_eq!(names(&out), vec![entry("a", None)]);
+    }
+
+    #[test]
+    fn insert_into_empty_lists() {
+        for deps in ["deps:\n  tools: []\n", "deps:\n  tools:\n", "deps:\n  other: 1\n"] {
+            let out = insert_tool(&doc(deps), &tool("a", None)).unwrap();
+            assert_eq!(names(&out), vec![entry("a", None)], "{}", deps);
+        }
+    }
+
+    #[test]
+    fn insert_keeps_comments() {
+        let out = insert_tool(&doc(COMMENTED), &tool("c", None)).unwrap();
+        for comment in ["# tools we need", "# pinned by hand", "# the first one", "# b is next", "# trailing"] {
+            assert!(out.contains(comment), "{}", comment);
+        }
+        assert_eq!(names(&out), vec![entry("a", None), entry("b", None), entry("c", None)]);
+    }
+
+    #[test]
+    fn remove_from_block() {
+        let out = remove_tool(&doc(BLOCK), "a").unwrap();
+        assert_eq!(
+            out,
+            doc("deps:\n  tools:\n    - name: b\n      version: \"1.0\"\n      link: https://example.com/b\n")
+        );
+    }
+
+    #[test]
+    fn remove_from_flow_items() {
+        let out = remove_tool(&doc(FLOW), "b").unwrap();
+        assert_eq!(out, doc("deps:\n  tools:\n    - {name: a, link: https://example.com/a}\n"));
+    }
+
+    #[test]
+    fn remove_keeps_comments() {
+        let out = remove_tool(&doc(COMMENTED), "b").unwrap();
+        assert!(out.contains("# the first one") && out.contains("# trailing"));
+        assert_eq!(names(&out), vec![entry("a", None)]);
+    }
+
+    #[test]
+    fn remove_missing_tool() {
+        assert!(remove_tool(&doc(BLOCK), "c").is_err());
+        assert!(remove_tool(&doc(""), "a").is_err());
+        assert!(remove_tool(&doc("deps:\n  tools: []\n"), "a").is_err());
+    }
+
+    #[test]
+    fn bump_version_in_block() {
+        let out = set_tool_version(&doc(BLOCK), "b", "2.0").unwrap();
+        assert_eq!(out, doc(&BLOCK.replace("\"1.0\"", "'2.0'")));
+        let out = set_tool_version(&doc(BLOCK), "a", "3").unwrap();
+        assert_eq!(names(&out), vec![entry("a", Some("3")), entry("b", Some("1.0"))]);
+    }
+
+    #[test]
+    fn bump_version_keeps_comments() {
+        let out = set_tool_version(&doc(COMMENTED), "a", "1").unwrap();
+        assert!(out.contains("- name: a # the first one"));
+        assert_eq!(names(&out), vec![entry("a", Some("1")), entry("b", None)]);
+    }
+
+    #[test]
+    fn bump_version_refuses_flow_items() {
+        assert!(set_tool_version(&doc(FLOW), "a", "1").is_err());
+    }
+
+    #[test]
+    fn bump_version_of_missing_tool() {
+        assert!(set_tool_version(&doc(BLOCK), "c", "1").is_err());
+        assert!(set_tool_version(&doc(""), "a", "1").is_err());
+    }
+
+    #[test]
+    fn keeps_crlf_newlines() {
+        let src = doc(BLOCK).replace('\n', "\r\n");
+        let out = insert_tool(&src, &tool("c", None)).unwrap();
+        assert!(!out.replace("\r\n", "").contains('\n'));
+    }
+
+    #[test]
+    fn refuses_invalid_documents() {
+        assert!(insert_tool("deps: [", &tool("a", None)).is_err());
+        assert!(remove_tool("not: a dreamfile\n", "a").is_err());
+    }
+}
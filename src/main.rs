@@ -6,7 +6,7 @@ extern crate serde_yaml;
 // Local imports
 pub mod helper;
 use helper::{
-    add, extension, help, invalid_args_notify, list, load, new,
+    add, extension, help, home::DreamerHome, invalid_args_notify, list, load, new,
     refs::{ADDCMD, HELPCMD, LISTCMD, LOADCMD, NEWCMD, RUNCMD},
     resource::argparse,
     run,
@@ -16,7 +16,7 @@ use helper::{
 use std::env::{self};
 use std::iter::*;
 
-use crate::helper::{refs::{EXTCMD, REMOVECMD}, remove, resource::scan_flags};
+use crate::helper::{fetch, gc, lock, refs::{EXTCMD, FETCHCMD, GCCMD, LOCKCMD, REMOVECMD}, remove, resource::scan_flags};
 /*
 Error codes:
 0000 OK
@@ -32,7 +32,7 @@ pub fn cli() {
     // Main cli function
     //env::set_var("RUST_BACKTRACE", "1");
     let args: Vec<String> = env::args().collect(); // Argument collection
-    let home_dir: Result<String, env::VarError> = env::var("HOME");
+    let home_dir: Result<DreamerHome, String> = DreamerHome::resolve();
     pub const ENV_COMMANDS: Vec<String> = vec![];
 
     let mut global_options: Vec<bool> = vec![false; 5];
@@ -41,6 +41,7 @@ pub fn cli() {
     0: verbose
     1: force
     2: dumb (no color)
+    3: offline (install from the download cache only)
      */
     scan_flags(&args, &mut global_options);
     if args.clone().len() == 1 {
@@ -81,9 +82,11 @@ pub fn cli() {
             _ if argparse(&args, 1, NEWCMD) => {
                 let _ = new(args, &global_options);
             }
-            _ if argparse(&args, 1, RUNCMD) => {
-                let _ = run(args, &global_options);
-            }
+            _ if argparse(&args, 1, RUNCMD) => match run(args, home_dir, &global_options) {
+                Ok(0) => {}
+                Ok(code) => std::process::exit(code),
+                Err(..) => std::process::exit(1),
+            },
             _ if argparse(&args, 1, HELPCMD) => {
                 help(args);
             }
@@ -101,7 +104,21 @@ pub fn cli() {
             }
             
             _ if argparse(&args, 1, REMOVECMD) => {
-                remove(args, &global_options);
+                remove(args, home_dir, &global_options);
+            }
+
+            _ if argparse(&args, 1, LOCKCMD) => {
+                let _ = lock(args, home_dir, &global_options);
+            }
+
+            _ if argparse(&args, 1, GCCMD) => {
+                let _ = gc(args, home_dir, &global_options);
+            }
+
+            _ if argparse(&args, 1, FETCHCMD) => {
+                if fetch(args, home_dir, &global_options).is_err() {
+                    std::process::exit(1);
+                }
             }
 
             _ => invalid_args_notify(args), // Create new plufile